@@ -1,14 +1,24 @@
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::net::{SocketAddr, UdpSocket};
 
-use robo_sim_utils::comms;
+use robo_sim_utils::comms::{self, CommsError, MsgHeader, WireMessage};
 use robo_sim_utils::messages::*;
+use robo_sim_utils::object_reading::*;
 use robo_sim_utils::robot_position::*;
 use robo_sim_utils::vec3d::*;
 
+use super::recording::{MsgDirection, RecordedMsg};
+
 pub struct ConsoleComms {
     sock: Option<UdpSocket>,
     addresses: HashMap<u32, SocketAddr>,
+    // sequence number for the next message sent, to any robot
+    next_seq: Cell<u32>,
+    reassembler: comms::Reassembler,
+    // everything sent and received since the last take_traffic(), if the
+    // run is being recorded
+    traffic: Option<RefCell<Vec<RecordedMsg>>>,
 }
 
 impl ConsoleComms {
@@ -16,6 +26,32 @@ impl ConsoleComms {
         Self {
             sock: None,
             addresses: HashMap::new(),
+            next_seq: Cell::new(1),
+            reassembler: comms::Reassembler::new(),
+            traffic: None,
+        }
+    }
+
+    pub fn set_recording(&mut self, recording: bool) {
+        self.traffic = if recording {
+            Some(RefCell::new(vec![]))
+        } else {
+            None
+        };
+    }
+
+    pub fn take_traffic(&mut self) -> Vec<RecordedMsg> {
+        match &mut self.traffic {
+            Some(traffic) => traffic.get_mut().drain(..).collect(),
+            None => vec![],
+        }
+    }
+
+    fn record_traffic(&self, direction: MsgDirection, id: u32, msg_buf: &[u8]) {
+        if let Some(traffic) = &self.traffic {
+            traffic
+                .borrow_mut()
+                .push(RecordedMsg::new(direction, id, msg_buf));
         }
     }
 
@@ -28,32 +64,54 @@ impl ConsoleComms {
         Ok(())
     }
 
-    pub fn check_for_msgs(&mut self) -> Vec<Box<dyn Message>> {
-        let mut msgs: Vec<Box<dyn Message>> = vec![];
+    /// Every message that's come in since the last call, each with its
+    /// header so requests can be answered.
+    pub fn check_for_msgs(&mut self) -> Vec<(MsgHeader, Box<dyn Message>)> {
+        let mut msgs: Vec<(MsgHeader, Box<dyn Message>)> = vec![];
 
         // make sure we the socket has been opened
         if self.sock.is_none() {
             return msgs;
         }
 
-        // keep going while there are messages waiting
+        // keep going while there are messages waiting; anything malformed
+        // gets dropped rather than taking the console down with it
         loop {
-            let mut buf = [0; 1024];
+            let mut buf = [0; comms::MAX_DATAGRAM_LEN];
             let msg_result = { self.sock.as_ref().unwrap().recv_from(&mut buf) };
             match msg_result {
                 Ok((received, addr)) => {
-                    let msg = comms::parse_message(&buf[..received]).unwrap();
+                    // only whole messages get recorded, along with anything
+                    // that couldn't be made sense of
+                    let whole_buf = match self.reassembler.add(&buf[..received]) {
+                        Ok(Some(whole_buf)) => whole_buf,
+                        Ok(None) => continue,
+                        Err(_) => buf[..received].to_vec(),
+                    };
+                    if self.traffic.is_some() {
+                        let id = self.id_for_addr(addr).unwrap_or(0);
+                        self.record_traffic(MsgDirection::Inbound, id, &whole_buf);
+                    }
+                    let (header, msg) = match comms::parse_message(&whole_buf) {
+                        Ok(parsed) => parsed,
+                        Err(err) => {
+                            println!("Error: dropping msg from {}: {}", addr, err);
+                            continue;
+                        }
+                    };
                     if msg.get_type() == MessageType::Alive {
-                        self.register_new_robot(&msg, addr);
+                        if let Err(err) = self.register_new_robot(&msg, addr) {
+                            println!("Error: dropping alive msg from {}: {}", addr, err);
+                            continue;
+                        }
                     }
-                    msgs.push(msg);
+                    msgs.push((header, msg));
                 }
                 Err(e) => {
-                    if e.kind() == std::io::ErrorKind::WouldBlock {
-                        break;
-                    } else {
-                        panic!("recv function failed: {:?}", e);
+                    if e.kind() != std::io::ErrorKind::WouldBlock {
+                        println!("Error: {}", CommsError::from(e));
                     }
+                    break;
                 }
             }
         }
@@ -61,55 +119,140 @@ impl ConsoleComms {
         msgs
     }
 
-    fn send_msg(&self, id: u32, msg_buf: &[u8]) {
-        let sock = self.sock.as_ref().unwrap();
-        let addr_result = self.addresses.get(&id);
-        if addr_result.is_none() {
-            panic!("uknown robot ID: {}", id);
+    fn send_msg(&self, id: u32, msg_buf: &[u8], reply_to: Option<u32>) -> Result<(), CommsError> {
+        let sock = self.sock.as_ref().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotConnected, "comms not open")
+        })?;
+        let addr = self
+            .addresses
+            .get(&id)
+            .ok_or(CommsError::UnknownRobot(id))?;
+
+        let seq = self.next_seq.get();
+        self.next_seq.set(comms::next_seq(seq));
+        let header = MsgHeader::new(comms::CONSOLE_ID, seq, reply_to, comms::timestamp_now_ms());
+        for datagram in comms::fragment_message(header, msg_buf)? {
+            sock.send_to(&datagram, addr)?;
         }
+        if self.traffic.is_some() {
+            let framed_buf = comms::frame_message(header, msg_buf);
+            self.record_traffic(MsgDirection::Outbound, id, &framed_buf);
+        }
+        Ok(())
+    }
 
-        if let Err(err) = sock.send_to(msg_buf, addr_result.unwrap()) {
-            panic!("send function failed: {:?}", err);
+    // a message that can't be sent is dropped, same as UDP would
+    fn send_or_log(&self, id: u32, msg_buf: &[u8], reply_to: Option<u32>) {
+        if let Err(err) = self.send_msg(id, msg_buf, reply_to) {
+            println!("Error: could not send to robot {}: {}", id, err);
         }
     }
 
-    fn register_new_robot(&mut self, msg: &Box<dyn Message>, addr: SocketAddr) {
+    fn id_for_addr(&self, addr: SocketAddr) -> Option<u32> {
+        self.addresses
+            .iter()
+            .find(|(_, robot_addr)| **robot_addr == addr)
+            .map(|(id, _)| *id)
+    }
+
+    fn register_new_robot(
+        &mut self,
+        msg: &Box<dyn Message>,
+        addr: SocketAddr,
+    ) -> Result<(), CommsError> {
         let alive_msg = downcast::<AliveMsg>(msg, "alive");
         if self.addresses.contains_key(&alive_msg.id) {
-            panic!("ID {} is being re-used.", alive_msg.id);
+            return Err(CommsError::DuplicateRobot(alive_msg.id));
         }
 
         self.addresses.insert(alive_msg.id, addr);
         println!("registered address for ID {}", alive_msg.id);
+        Ok(())
     }
 
     pub fn unregister_robot(&mut self, id: u32) {
         self.addresses.remove(&id);
     }
 
-    pub fn send_start_msg(&self, id: u32) {
-        let msg = StartMsg::new();
-        let msg_buf = comms::pack_start_message(msg);
-        self.send_msg(id, &msg_buf);
+    pub fn send_start_msg(&self, id: u32, lockstep: bool) {
+        let msg = StartMsg::new(lockstep);
+        let msg_buf = msg.pack();
+        self.send_or_log(id, &msg_buf, None);
     }
 
     pub fn send_pause_msg(&self) {
         let msg = PauseMsg::new();
-        let msg_buf = comms::pack_pause_message(msg);
+        let msg_buf = msg.pack();
         for id in self.addresses.keys() {
-            self.send_msg(*id, &msg_buf);
+            self.send_or_log(*id, &msg_buf, None);
         }
     }
 
-    pub fn send_position_msg(&self, id: u32, pos: RobotPosition) {
-        let msg = PositionMsg::new(id, pos);
-        let msg_buf = comms::pack_position_message(msg);
-        self.send_msg(id, &msg_buf);
+    pub fn send_kill_msg(&self, id: u32) {
+        if !self.addresses.contains_key(&id) {
+            println!("Error: tried to kill an unregistered robot: {}", id);
+            return;
+        }
+
+        let msg = KillMsg::new();
+        let msg_buf = msg.pack();
+        self.send_or_log(id, &msg_buf, None);
+    }
+
+    pub fn send_kill_all_msg(&self) {
+        let msg = KillMsg::new();
+        let msg_buf = msg.pack();
+        for id in self.addresses.keys() {
+            self.send_or_log(*id, &msg_buf, None);
+        }
+    }
+
+    /// Sends a robot its position. When it's answering a request, reply_to is
+    /// that request's seq, and the replies below take the same.
+    pub fn send_position_msg(
+        &self,
+        id: u32,
+        pos: RobotPosition,
+        sim_time: f64,
+        reply_to: Option<u32>,
+    ) {
+        let msg = PositionMsg::new(id, pos, sim_time);
+        let msg_buf = msg.pack();
+        self.send_or_log(id, &msg_buf, reply_to);
     }
 
-    pub fn send_obs_readings_msg(&self, id: u32, obs_readings: Vec<Vec3d<f32>>) {
+    pub fn send_obs_readings_msg(&self, id: u32, obs_readings: Vec<Vec3d<f32>>, request_seq: u32) {
         let msg = ObsReadingsMsg::new(obs_readings);
-        let msg_buf = comms::pack_obs_readings_message(msg);
-        self.send_msg(id, &msg_buf);
+        let msg_buf = msg.pack();
+        self.send_or_log(id, &msg_buf, Some(request_seq));
+    }
+
+    pub fn send_object_readings_msg(
+        &self,
+        id: u32,
+        object_readings: Vec<ObjectReading>,
+        request_seq: u32,
+    ) {
+        let msg = ObjectReadingsMsg::new(object_readings);
+        let msg_buf = msg.pack();
+        self.send_or_log(id, &msg_buf, Some(request_seq));
+    }
+
+    pub fn send_carry_status_msg(&self, id: u32, carrying: bool, request_seq: u32) {
+        let msg = CarryStatusMsg::new(id, carrying);
+        let msg_buf = msg.pack();
+        self.send_or_log(id, &msg_buf, Some(request_seq));
+    }
+
+    pub fn send_goal_event_msg(&self, id: u32, goal_id: u32, entered: bool, elapsed_s: f32) {
+        let msg = GoalEventMsg::new(id, goal_id, entered, elapsed_s);
+        let msg_buf = msg.pack();
+        self.send_or_log(id, &msg_buf, None);
+    }
+
+    pub fn send_tick_msg(&self, id: u32, tick: u64, sim_time: f64) {
+        let msg = TickMsg::new(tick, sim_time);
+        let msg_buf = msg.pack();
+        self.send_or_log(id, &msg_buf, None);
     }
 }
@@ -1,17 +1,190 @@
+use std::collections::{HashMap, VecDeque};
+
 use piston_window as pw;
+use pw::{MouseCursorEvent, MouseScrollEvent, PressEvent, ReleaseEvent, ResizeEvent};
 
+use robo_sim_utils::color::Color;
 use robo_sim_utils::vec3d::Vec3d;
 
 use super::environment as environ;
 
+/// The optional things drawn over the environment, and the history the
+/// trails need.
+pub struct Layers {
+    pub show_trails: bool,
+    pub show_sensor_rays: bool,
+    pub show_readings: bool,
+    trails: HashMap<u32, VecDeque<Vec3d<f32>>>,
+}
+
+impl Layers {
+    const MAX_TRAIL_POINTS: usize = 500;
+    // how far a robot has to move before its trail gets another point
+    const MIN_TRAIL_STEP: f32 = 0.1;
+
+    pub fn new() -> Self {
+        Self {
+            show_trails: true,
+            show_sensor_rays: false,
+            show_readings: true,
+            trails: HashMap::new(),
+        }
+    }
+
+    /// T toggles trails, R sensor rays and O obstacle readings. Returns false
+    /// for any other key.
+    pub fn toggle_for_key(&mut self, key: pw::Key) -> bool {
+        let (layer, name) = match key {
+            pw::Key::T => (&mut self.show_trails, "trails"),
+            pw::Key::R => (&mut self.show_sensor_rays, "sensor rays"),
+            pw::Key::O => (&mut self.show_readings, "obstacle readings"),
+            _ => return false,
+        };
+        *layer = !*layer;
+        println!("{} {}", name, if *layer { "on" } else { "off" });
+        true
+    }
+
+    pub fn clear_trails(&mut self) {
+        self.trails.clear();
+    }
+
+    fn update_trails(&mut self, env: &environ::Environment) {
+        self.trails.retain(|id, _| env.robots.contains_key(id));
+
+        for robot in env.robots.values() {
+            let trail = self.trails.entry(robot.id).or_default();
+            let location = robot.pos.location;
+            let moved = match trail.back() {
+                Some(last) => (location - *last).len() >= Layers::MIN_TRAIL_STEP,
+                None => true,
+            };
+            if moved {
+                if trail.len() == Layers::MAX_TRAIL_POINTS {
+                    trail.pop_front();
+                }
+                trail.push_back(location);
+            }
+        }
+    }
+}
+
+/// Turns mouse and window events into panning and zooming of the view:
+/// dragging pans, the scroll wheel zooms around the cursor, and F fits the
+/// whole environment back into the window.
+pub struct ViewControl {
+    window_size: [f64; 2],
+    cursor: [f64; 2],
+    dragging: bool,
+    // the editor wants the left button to itself
+    pan_with_left: bool,
+    // still showing the whole environment, so resizing should refit it
+    fitted: bool,
+}
+
+impl ViewControl {
+    const ZOOM_PER_SCROLL_STEP: f32 = 1.1;
+
+    pub fn new(window_size: [f64; 2]) -> Self {
+        Self {
+            window_size: window_size,
+            cursor: [0.0, 0.0],
+            dragging: false,
+            pan_with_left: true,
+            fitted: true,
+        }
+    }
+
+    pub fn set_pan_with_left(&mut self, pan_with_left: bool) {
+        self.pan_with_left = pan_with_left;
+        self.dragging = false;
+    }
+
+    /// The scale that fits the environment in the window as it starts out.
+    pub fn fitted_scale(&self, env: &environ::Environment) -> environ::EnvironmentScale {
+        environ::EnvironmentScale::from_env_size(
+            self.window_size[0] as f32,
+            self.window_size[1] as f32,
+            env.width_m,
+            env.height_m,
+        )
+    }
+
+    pub fn handle_event(&mut self, event: &pw::Event, scale: &mut environ::EnvironmentScale) {
+        if let Some(cursor) = event.mouse_cursor_args() {
+            if self.dragging {
+                let dx = (cursor[0] - self.cursor[0]) as f32;
+                let dy = (cursor[1] - self.cursor[1]) as f32;
+                scale.pan(dx, dy);
+                self.fitted = false;
+            }
+            self.cursor = cursor;
+        }
+
+        if let Some(scroll) = event.mouse_scroll_args() {
+            let factor = ViewControl::ZOOM_PER_SCROLL_STEP.powf(scroll[1] as f32);
+            scale.zoom_about(factor, self.cursor[0] as f32, self.cursor[1] as f32);
+            self.fitted = false;
+        }
+
+        match event.press_args() {
+            Some(pw::Button::Mouse(pw::MouseButton::Left)) if self.pan_with_left => {
+                self.dragging = true
+            }
+            Some(pw::Button::Mouse(pw::MouseButton::Middle)) => self.dragging = true,
+            Some(pw::Button::Keyboard(pw::Key::F)) => {
+                *scale = self.refit(scale);
+                self.fitted = true;
+            }
+            _ => {}
+        }
+
+        match event.release_args() {
+            Some(pw::Button::Mouse(pw::MouseButton::Left))
+            | Some(pw::Button::Mouse(pw::MouseButton::Middle)) => self.dragging = false,
+            _ => {}
+        }
+
+        if let Some(args) = event.resize_args() {
+            let old_size = self.window_size;
+            self.window_size = args.window_size;
+            if self.fitted {
+                *scale = self.refit(scale);
+            } else {
+                // keep whatever was in the middle of the window there
+                let dx = (self.window_size[0] - old_size[0]) / 2.0;
+                let dy = (self.window_size[1] - old_size[1]) / 2.0;
+                scale.pan(dx as f32, dy as f32);
+            }
+        }
+    }
+
+    fn refit(&self, scale: &environ::EnvironmentScale) -> environ::EnvironmentScale {
+        environ::EnvironmentScale::from_env_size(
+            self.window_size[0] as f32,
+            self.window_size[1] as f32,
+            scale.env_width_m,
+            scale.env_height_m,
+        )
+    }
+}
+
 pub fn draw_env(
     env: &environ::Environment,
     scale: &environ::EnvironmentScale,
+    layers: &mut Layers,
     context: pw::Context,
     graphics: &mut pw::G2d<'_>,
 ) {
+    // kept up to date even while hidden, so turning them on shows the history
+    layers.update_trails(env);
+
     pw::clear([1.0; 4], graphics);
 
+    for goal in &env.goals {
+        draw_goal(goal, scale, context, graphics);
+    }
+
     for obs in &env.obstacles {
         draw_obstacle(obs, scale, context, graphics);
     }
@@ -20,21 +193,140 @@ pub fn draw_env(
         draw_wall(wall, scale, context, graphics);
     }
 
+    for polygon in &env.polygons {
+        draw_filled_polygon(&polygon.points, pw::color::BLACK, scale, context, graphics);
+    }
+
     for object in &env.objects {
         draw_object(object, scale, context, graphics);
     }
 
+    if layers.show_trails {
+        for (id, trail) in &layers.trails {
+            draw_trail(trail, env.robots[id].color, scale, context, graphics);
+        }
+    }
+
+    if layers.show_sensor_rays {
+        for (id, single_robot_readings) in &env.obstacle_readings {
+            if let Some(robot) = env.robots.get(id) {
+                for reading in single_robot_readings.iter() {
+                    draw_sensor_ray(robot, reading, scale, context, graphics);
+                }
+            }
+        }
+    }
+
     for robot in env.robots.values() {
         draw_robot(robot, scale, context, graphics);
     }
 
-    for single_robot_readings in env.obstacle_readings.values() {
-        for reading in single_robot_readings.iter() {
-            draw_obstacle_reading(reading, scale, context, graphics);
+    if layers.show_readings {
+        for single_robot_readings in env.obstacle_readings.values() {
+            for reading in single_robot_readings.iter() {
+                draw_obstacle_reading(reading, scale, context, graphics);
+            }
         }
     }
 }
 
+fn draw_trail(
+    trail: &VecDeque<Vec3d<f32>>,
+    color: Color,
+    scale: &environ::EnvironmentScale,
+    context: pw::Context,
+    graphics: &mut pw::G2d<'_>,
+) {
+    // faded, so the trail doesn't get mistaken for a wall
+    let color = [
+        color.r as f32 / 255.0,
+        color.g as f32 / 255.0,
+        color.b as f32 / 255.0,
+        color.a as f32 / 255.0 * 0.5,
+    ];
+    for (p1, p2) in trail.iter().zip(trail.iter().skip(1)) {
+        let (x1, y1) = scale.coords_meters_to_pixels(p1.x, p1.y);
+        let (x2, y2) = scale.coords_meters_to_pixels(p2.x, p2.y);
+        pw::line(
+            color,
+            1.0,
+            [x1 as f64, y1 as f64, x2 as f64, y2 as f64],
+            context.transform,
+            graphics,
+        );
+    }
+}
+
+fn draw_sensor_ray(
+    robot: &environ::Robot,
+    reading: &Vec3d<f32>,
+    scale: &environ::EnvironmentScale,
+    context: pw::Context,
+    graphics: &mut pw::G2d<'_>,
+) {
+    const RAY_COLOR: [f32; 4] = [1.0, 0.0, 0.0, 0.3];
+    let from = robot.pos.location;
+    let (x1, y1) = scale.coords_meters_to_pixels(from.x, from.y);
+    let (x2, y2) = scale.coords_meters_to_pixels(reading.x, reading.y);
+    pw::line(
+        RAY_COLOR,
+        0.5,
+        [x1 as f64, y1 as f64, x2 as f64, y2 as f64],
+        context.transform,
+        graphics,
+    );
+}
+
+fn draw_goal(
+    goal: &environ::Goal,
+    scale: &environ::EnvironmentScale,
+    context: pw::Context,
+    graphics: &mut pw::G2d<'_>,
+) {
+    let color = [
+        goal.color.r as f32 / 255.0,
+        goal.color.g as f32 / 255.0,
+        goal.color.b as f32 / 255.0,
+        goal.color.a as f32 / 255.0,
+    ];
+    match &goal.shape {
+        environ::GoalShape::Circle { x, y, radius } => {
+            let (x, y) = scale.coords_meters_to_pixels(*x, *y);
+            let r = scale.dist_meters_to_pixels(*radius) as f64;
+            pw::ellipse(
+                color,
+                [(x as f64 - r), (y as f64 - r), r * 2.0, r * 2.0],
+                context.transform,
+                graphics,
+            );
+        }
+        environ::GoalShape::Polygon(points) => {
+            draw_filled_polygon(points, color, scale, context, graphics);
+        }
+    }
+}
+
+// piston fills polygons as a fan from the first point, which is only right for
+// convex shapes, so concave ones get split into triangles first
+fn draw_filled_polygon(
+    points: &[Vec3d<f32>],
+    color: [f32; 4],
+    scale: &environ::EnvironmentScale,
+    context: pw::Context,
+    graphics: &mut pw::G2d<'_>,
+) {
+    for triangle in environ::triangulate_polygon(points) {
+        let coords: Vec<[f64; 2]> = triangle
+            .iter()
+            .map(|p| {
+                let (x, y) = scale.coords_meters_to_pixels(p.x, p.y);
+                [x as f64, y as f64]
+            })
+            .collect();
+        pw::polygon(color, &coords, context.transform, graphics);
+    }
+}
+
 fn draw_obstacle(
     obs: &environ::Obstacle,
     scale: &environ::EnvironmentScale,
@@ -51,6 +343,25 @@ fn draw_obstacle(
     );
 }
 
+/// Draws a wall that's still being dragged out in the editor, in grey so it
+/// stands out from the real ones.
+pub fn draw_pending_wall(
+    wall: &environ::Wall,
+    scale: &environ::EnvironmentScale,
+    context: pw::Context,
+    graphics: &mut pw::G2d<'_>,
+) {
+    let (x1, y1) = scale.coords_meters_to_pixels(wall.x1, wall.y1);
+    let (x2, y2) = scale.coords_meters_to_pixels(wall.x2, wall.y2);
+    pw::line(
+        [0.5, 0.5, 0.5, 1.0],
+        1.0,
+        [x1 as f64, y1 as f64, x2 as f64, y2 as f64],
+        context.transform,
+        graphics,
+    );
+}
+
 fn draw_wall(
     wall: &environ::Wall,
     scale: &environ::EnvironmentScale,
@@ -130,6 +441,14 @@ fn draw_robot(
     ];
 
     pw::polygon(color, &coords, context.transform, graphics);
+
+    // anything being carried rides just in front of the robot's nose
+    if let Some(object) = robot.carrying {
+        let offset = Vec3d::<f32>::new(1.0 + object.radius, 0.0, 0.0);
+        let location = offset.rotated_z(robot.pos.heading_rad) + robot.pos.location;
+        let carried = environ::Object::new(location.x, location.y, object.radius, object.color);
+        draw_object(&carried, scale, context, graphics);
+    }
 }
 
 fn draw_obstacle_reading(
@@ -147,3 +466,36 @@ fn draw_obstacle_reading(
         graphics,
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use robo_sim_utils::robot_position::RobotPosition;
+
+    #[test]
+    fn test_trails_are_bounded_and_follow_robots() {
+        let mut env = environ::Environment::new(100.0, 100.0);
+        let mut layers = Layers::new();
+        let pos = RobotPosition::new(Vec3d::new(0.0, 0.0, 0.0), 0.0);
+        env.add_robot(environ::Robot::new(1, pos, Color::BLUE));
+
+        // standing still doesn't add points
+        layers.update_trails(&env);
+        layers.update_trails(&env);
+        assert_eq!(layers.trails[&1].len(), 1);
+
+        for i in 1..=Layers::MAX_TRAIL_POINTS {
+            let pos = RobotPosition::new(Vec3d::new(i as f32 * 0.2, 0.0, 0.0), 0.0);
+            env.update_robot_pos(1, pos);
+            layers.update_trails(&env);
+        }
+        let trail = &layers.trails[&1];
+        assert_eq!(trail.len(), Layers::MAX_TRAIL_POINTS);
+        assert!(trail.front().unwrap().x > 0.0);
+
+        env.remove_robot(1);
+        layers.update_trails(&env);
+        assert!(layers.trails.is_empty());
+    }
+}
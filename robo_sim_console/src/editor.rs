@@ -0,0 +1,354 @@
+use piston_window as pw;
+use pw::{MouseCursorEvent, PressEvent, ReleaseEvent};
+
+use robo_sim_utils::color::Color;
+use robo_sim_utils::vec3d::Vec3d;
+
+use super::console_comms::ConsoleComms;
+use super::environment::*;
+use super::scenario::{RobotSpawn, Scenario};
+use super::simulator::Simulator;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Tool {
+    Obstacle,
+    Object,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Drag {
+    // starts out as a click, and becomes a wall once it's moved far enough
+    Wall {
+        from: Vec3d<f32>,
+        from_pix: [f64; 2],
+    },
+    Robot {
+        id: u32,
+    },
+}
+
+/// Mouse-driven changes to the environment while the console is running.
+/// Left-clicking places the current tool's shape, left-dragging draws a wall
+/// (or moves a robot, if it starts on one) and right-clicking removes
+/// whatever's under the cursor.
+pub struct Editor {
+    active: bool,
+    tool: Tool,
+    cursor: [f64; 2],
+    drag: Option<Drag>,
+}
+
+impl Editor {
+    const OBSTACLE_RADIUS: f32 = 1.0;
+    const OBJECT_RADIUS: f32 = 0.5;
+    const OBJECT_COLOR: Color = Color::RED;
+    // robots are drawn about this big, whatever their actual radius
+    const ROBOT_PICK_RADIUS: f32 = 0.75;
+    // anything shorter is a click rather than a wall
+    const MIN_WALL_DRAG_PIX: f64 = 5.0;
+    const WALL_PICK_DIST_PIX: f32 = 5.0;
+
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            tool: Tool::Obstacle,
+            cursor: [0.0, 0.0],
+            drag: None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn toggle_active(&mut self) {
+        self.active = !self.active;
+        self.drag = None;
+        if self.active {
+            println!(
+                "editing: click places {:?}s, drag draws walls or moves robots,",
+                self.tool
+            );
+            println!("  right-click removes, tab switches tools, s saves the scenario");
+        } else {
+            println!("done editing");
+        }
+    }
+
+    pub fn next_tool(&mut self) {
+        self.tool = match self.tool {
+            Tool::Obstacle => Tool::Object,
+            Tool::Object => Tool::Obstacle,
+        };
+        println!("click places {:?}s", self.tool);
+    }
+
+    /// The wall being dragged out, if there is one, so it can be drawn.
+    pub fn pending_wall(&self, scale: &EnvironmentScale) -> Option<Wall> {
+        match self.drag {
+            Some(Drag::Wall { from, from_pix }) if self.is_wall_drag(from_pix) => {
+                let to = self.cursor_meters(scale);
+                Some(Wall::new(from.x, from.y, to.x, to.y))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn handle_event(
+        &mut self,
+        event: &pw::Event,
+        scale: &EnvironmentScale,
+        env: &mut Environment,
+        simulator: &mut Simulator,
+        comms: &ConsoleComms,
+    ) {
+        if let Some(cursor) = event.mouse_cursor_args() {
+            self.cursor = cursor;
+            if let (true, Some(Drag::Robot { id })) = (self.active, self.drag) {
+                self.move_robot(id, scale, env, simulator);
+            }
+        }
+
+        if !self.active {
+            return;
+        }
+
+        match event.press_args() {
+            Some(pw::Button::Mouse(pw::MouseButton::Left)) => {
+                let point = self.cursor_meters(scale);
+                self.drag = match robot_at(env, point) {
+                    Some(id) => Some(Drag::Robot { id: id }),
+                    None => Some(Drag::Wall {
+                        from: point,
+                        from_pix: self.cursor,
+                    }),
+                };
+            }
+            Some(pw::Button::Mouse(pw::MouseButton::Right)) => {
+                self.remove_at(self.cursor_meters(scale), scale, env);
+            }
+            _ => {}
+        }
+
+        if let Some(pw::Button::Mouse(pw::MouseButton::Left)) = event.release_args() {
+            match self.drag.take() {
+                Some(Drag::Robot { id }) => {
+                    // let the robot know it's been moved
+                    if let Some(pos) = simulator.get_robot_pos(id) {
+                        comms.send_position_msg(id, pos, simulator.sim_time(), None);
+                    }
+                }
+                Some(Drag::Wall { from, from_pix }) => {
+                    let to = self.cursor_meters(scale);
+                    if self.is_wall_drag(from_pix) {
+                        self.add_wall(from, to, env);
+                    } else {
+                        self.place(to, env);
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn cursor_meters(&self, scale: &EnvironmentScale) -> Vec3d<f32> {
+        let (x, y) = scale.coords_pixels_to_meters(self.cursor[0] as f32, self.cursor[1] as f32);
+        Vec3d::new(x, y, 0.0)
+    }
+
+    fn is_wall_drag(&self, from_pix: [f64; 2]) -> bool {
+        let dx = self.cursor[0] - from_pix[0];
+        let dy = self.cursor[1] - from_pix[1];
+        dx.hypot(dy) >= Editor::MIN_WALL_DRAG_PIX
+    }
+
+    fn move_robot(
+        &self,
+        id: u32,
+        scale: &EnvironmentScale,
+        env: &mut Environment,
+        simulator: &mut Simulator,
+    ) {
+        let location = clamp_to_env(env, self.cursor_meters(scale));
+        // somewhere it doesn't fit leaves it where it last did
+        simulator.teleport_robot(id, location, env);
+    }
+
+    fn place(&self, point: Vec3d<f32>, env: &mut Environment) {
+        if !is_in_env(env, point) {
+            return;
+        }
+
+        match self.tool {
+            Tool::Obstacle => {
+                env.add_obstacle(Obstacle::new(point.x, point.y, Editor::OBSTACLE_RADIUS));
+            }
+            Tool::Object => {
+                let object = Object::new(
+                    point.x,
+                    point.y,
+                    Editor::OBJECT_RADIUS,
+                    Editor::OBJECT_COLOR,
+                );
+                env.add_object(object);
+            }
+        }
+    }
+
+    fn add_wall(&self, from: Vec3d<f32>, to: Vec3d<f32>, env: &mut Environment) {
+        let from = clamp_to_env(env, from);
+        let to = clamp_to_env(env, to);
+        if from != to {
+            env.add_wall(Wall::new(from.x, from.y, to.x, to.y));
+        }
+    }
+
+    // objects are drawn on top, so they go first
+    fn remove_at(&self, point: Vec3d<f32>, scale: &EnvironmentScale, env: &mut Environment) {
+        if let Some(idx) = env
+            .objects
+            .iter()
+            .position(|object| (Vec3d::new(object.x, object.y, 0.0) - point).len() <= object.radius)
+        {
+            env.objects.remove(idx);
+            return;
+        }
+
+        if let Some(idx) = env
+            .obstacles
+            .iter()
+            .position(|obs| (Vec3d::new(obs.x, obs.y, 0.0) - point).len() <= obs.radius)
+        {
+            env.obstacles.remove(idx);
+            env.rebuild_shape_index();
+            return;
+        }
+
+        let max_dist = Editor::WALL_PICK_DIST_PIX / scale.pix_per_meter;
+        if let Some(idx) = env
+            .walls
+            .iter()
+            .position(|wall| dist_to_wall(wall, point) <= max_dist)
+        {
+            env.walls.remove(idx);
+            env.rebuild_shape_index();
+        }
+    }
+}
+
+/// The scenario as it stands now: the edited environment, with anything being
+/// carried put back down, and robots starting wherever they are.
+pub fn edited_scenario(env: &Environment, spawns: &[RobotSpawn], seed: u64) -> Scenario {
+    let mut scenario_env = env.clone();
+    scenario_env.robots.clear();
+    scenario_env.obstacle_readings.clear();
+    for robot in env.robots.values() {
+        if let Some(mut object) = robot.carrying {
+            object.x = robot.pos.location.x;
+            object.y = robot.pos.location.y;
+            scenario_env.add_object(object);
+        }
+    }
+
+    let robots = spawns
+        .iter()
+        .map(|spawn| match env.robots.get(&spawn.id) {
+            Some(robot) => RobotSpawn {
+                pos: robot.pos,
+                ..*spawn
+            },
+            None => *spawn,
+        })
+        .collect();
+
+    Scenario {
+        env: scenario_env,
+        robots: robots,
+        seed: Some(seed),
+    }
+}
+
+fn robot_at(env: &Environment, point: Vec3d<f32>) -> Option<u32> {
+    env.robots
+        .values()
+        .find(|robot| (robot.pos.location - point).len() <= Editor::ROBOT_PICK_RADIUS)
+        .map(|robot| robot.id)
+}
+
+fn is_in_env(env: &Environment, point: Vec3d<f32>) -> bool {
+    point.x >= 0.0 && point.x <= env.width_m && point.y >= 0.0 && point.y <= env.height_m
+}
+
+fn clamp_to_env(env: &Environment, point: Vec3d<f32>) -> Vec3d<f32> {
+    Vec3d::new(
+        point.x.clamp(0.0, env.width_m),
+        point.y.clamp(0.0, env.height_m),
+        0.0,
+    )
+}
+
+fn dist_to_wall(wall: &Wall, point: Vec3d<f32>) -> f32 {
+    let start = Vec3d::new(wall.x1, wall.y1, 0.0);
+    let end = Vec3d::new(wall.x2, wall.y2, 0.0);
+    dist_to_segment(start, end, point)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_place_and_remove() {
+        let mut env = Environment::new(20.0, 20.0);
+        let scale = EnvironmentScale::new(20.0, 20.0, 20.0);
+        let mut editor = Editor::new();
+
+        editor.place(Vec3d::new(5.0, 5.0, 0.0), &mut env);
+        editor.next_tool();
+        editor.place(Vec3d::new(10.0, 10.0, 0.0), &mut env);
+        // nothing goes outside the environment
+        editor.place(Vec3d::new(25.0, 10.0, 0.0), &mut env);
+        assert_eq!(env.obstacles, vec![Obstacle::new(5.0, 5.0, 1.0)]);
+        assert_eq!(env.objects.len(), 1);
+
+        editor.add_wall(
+            Vec3d::new(-5.0, 2.0, 0.0),
+            Vec3d::new(8.0, 2.0, 0.0),
+            &mut env,
+        );
+        assert_eq!(env.walls, vec![Wall::new(0.0, 2.0, 8.0, 2.0)]);
+
+        editor.remove_at(Vec3d::new(10.2, 10.2, 0.0), &scale, &mut env);
+        assert!(env.objects.is_empty());
+        editor.remove_at(Vec3d::new(4.5, 5.0, 0.0), &scale, &mut env);
+        assert!(env.obstacles.is_empty());
+        editor.remove_at(Vec3d::new(4.0, 2.1, 0.0), &scale, &mut env);
+        assert!(env.walls.is_empty());
+
+        // and the index knows they're gone
+        let ray =
+            robo_sim_utils::ray::Ray::new(Vec3d::new(1.0, 5.0, 0.0), Vec3d::new(2.0, 5.0, 0.0));
+        assert!(env.closest_shape_hit(&ray, 100.0).is_none());
+    }
+
+    #[test]
+    fn test_edited_scenario() {
+        let mut scenario = Scenario::default_scenario();
+        let spawn = scenario.robots[0];
+        let mut moved = spawn.pos;
+        moved.location = Vec3d::new(3.0, 4.0, 0.0);
+        let mut robot = Robot::new(spawn.id, moved, spawn.color);
+        robot.carrying = Some(Object::new(0.0, 0.0, 0.5, Color::RED));
+        scenario.env.add_robot(robot);
+
+        let edited = edited_scenario(&scenario.env, &scenario.robots, 7);
+        assert!(edited.env.robots.is_empty());
+        assert_eq!(
+            edited.env.objects.last(),
+            Some(&Object::new(3.0, 4.0, 0.5, Color::RED))
+        );
+        assert_eq!(edited.robots[0].pos, moved);
+        assert_eq!(edited.robots[1..], scenario.robots[1..]);
+        assert_eq!(edited.seed, Some(7));
+    }
+}
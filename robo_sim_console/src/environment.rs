@@ -1,10 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use robo_sim_utils::color::*;
 use robo_sim_utils::ray::*;
 use robo_sim_utils::robot_position::*;
 use robo_sim_utils::vec3d::*;
 
+use super::spatial_index::*;
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Obstacle {
     pub x: f32,
@@ -24,6 +26,17 @@ impl Obstacle {
     pub fn intersect_with_ray(&self, ray: &Ray<f32>) -> Option<Vec3d<f32>> {
         ray.intersect_with_circle_2d(self.x, self.y, self.radius)
     }
+
+    pub fn sweep_circle(&self, ray: &Ray<f32>, radius: f32) -> Option<SweepHit<f32>> {
+        ray.sweep_circle_against_circle_2d(radius, self.x, self.y, self.radius)
+    }
+
+    pub fn bounds(&self) -> (Vec3d<f32>, Vec3d<f32>) {
+        (
+            Vec3d::new(self.x - self.radius, self.y - self.radius, 0.0),
+            Vec3d::new(self.x + self.radius, self.y + self.radius, 0.0),
+        )
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -66,6 +79,184 @@ impl Wall {
     pub fn intersect_with_ray(&self, ray: &Ray<f32>) -> Option<Vec3d<f32>> {
         ray.intersect_with_segment_2d(self.x1, self.y1, self.x2, self.y2)
     }
+
+    pub fn sweep_circle(&self, ray: &Ray<f32>, radius: f32) -> Option<SweepHit<f32>> {
+        ray.sweep_circle_against_segment_2d(radius, self.x1, self.y1, self.x2, self.y2)
+    }
+
+    pub fn bounds(&self) -> (Vec3d<f32>, Vec3d<f32>) {
+        (
+            Vec3d::new(self.x1.min(self.x2), self.y1.min(self.y2), 0.0),
+            Vec3d::new(self.x1.max(self.x2), self.y1.max(self.y2), 0.0),
+        )
+    }
+}
+
+/// A closed obstacle outline, convex or concave. The last point connects back
+/// to the first.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Polygon {
+    pub points: Vec<Vec3d<f32>>,
+}
+
+impl Polygon {
+    pub fn new(points: Vec<Vec3d<f32>>) -> Self {
+        Self { points: points }
+    }
+
+    pub fn intersect_with_ray(&self, ray: &Ray<f32>) -> Option<Vec3d<f32>> {
+        ray.intersect_with_polygon_2d(&self.points)
+    }
+
+    pub fn sweep_circle(&self, ray: &Ray<f32>, radius: f32) -> Option<SweepHit<f32>> {
+        ray.sweep_circle_against_polygon_2d(radius, &self.points)
+    }
+
+    pub fn contains(&self, point: Vec3d<f32>) -> bool {
+        polygon_contains(&self.points, point)
+    }
+
+    pub fn bounds(&self) -> (Vec3d<f32>, Vec3d<f32>) {
+        let inf = f32::INFINITY;
+        self.points.iter().fold(
+            (Vec3d::new(inf, inf, 0.0), Vec3d::new(-inf, -inf, 0.0)),
+            |(min, max), p| {
+                (
+                    Vec3d::new(min.x.min(p.x), min.y.min(p.y), 0.0),
+                    Vec3d::new(max.x.max(p.x), max.y.max(p.y), 0.0),
+                )
+            },
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum GoalShape {
+    Circle { x: f32, y: f32, radius: f32 },
+    Polygon(Vec<Vec3d<f32>>),
+}
+
+/// A named region robots are trying to reach. The simulator tracks when each
+/// robot's center enters and leaves it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Goal {
+    pub name: String,
+    pub shape: GoalShape,
+    pub color: Color,
+    pub notify: bool,
+}
+
+impl Goal {
+    pub fn new(name: &str, shape: GoalShape, color: Color, notify: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            shape: shape,
+            color: color,
+            notify: notify,
+        }
+    }
+
+    pub fn contains(&self, point: Vec3d<f32>) -> bool {
+        match &self.shape {
+            GoalShape::Circle { x, y, radius } => {
+                let (dx, dy) = (point.x - x, point.y - y);
+                (dx * dx) + (dy * dy) <= radius * radius
+            }
+            GoalShape::Polygon(points) => polygon_contains(points, point),
+        }
+    }
+}
+
+// even-odd rule: count how many edges a ray running +x from the point crosses
+fn polygon_contains(points: &[Vec3d<f32>], point: Vec3d<f32>) -> bool {
+    let mut inside = false;
+    for i in 0..points.len() {
+        let p1 = points[i];
+        let p2 = points[(i + 1) % points.len()];
+        if (p1.y > point.y) != (p2.y > point.y) {
+            let x_cross = p1.x + (point.y - p1.y) * (p2.x - p1.x) / (p2.y - p1.y);
+            if point.x < x_cross {
+                inside = !inside;
+            }
+        }
+    }
+
+    inside
+}
+
+/// How far the point is from the nearest part of the segment.
+pub fn dist_to_segment(start: Vec3d<f32>, end: Vec3d<f32>, point: Vec3d<f32>) -> f32 {
+    let along = end - start;
+    if along.len_sq() == 0.0 {
+        return (point - start).len();
+    }
+    let t = ((point - start).dot(along) / along.len_sq()).clamp(0.0, 1.0);
+    (start + along * t - point).len()
+}
+
+/// Splits a simple polygon (convex or not) into triangles by ear clipping, so
+/// it can be filled by something that only knows about convex shapes.
+pub fn triangulate_polygon(points: &[Vec3d<f32>]) -> Vec<[Vec3d<f32>; 3]> {
+    let signed_area: f32 = (0..points.len())
+        .map(|i| {
+            let (p1, p2) = (points[i], points[(i + 1) % points.len()]);
+            (p1.x * p2.y) - (p2.x * p1.y)
+        })
+        .sum();
+    let ccw = signed_area > 0.0;
+
+    let cross = |a: Vec3d<f32>, b: Vec3d<f32>, c: Vec3d<f32>| {
+        ((b.x - a.x) * (c.y - a.y)) - ((b.y - a.y) * (c.x - a.x))
+    };
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len().saturating_sub(2));
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (ia, ib, ic) = (
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            );
+            let (a, b, c) = (points[ia], points[ib], points[ic]);
+            let turn = cross(a, b, c);
+            let convex = if ccw { turn > 0.0 } else { turn < 0.0 };
+            convex
+                && !remaining.iter().any(|&j| {
+                    if j == ia || j == ib || j == ic {
+                        return false;
+                    }
+                    let p = points[j];
+                    let (d1, d2, d3) = (cross(a, b, p), cross(b, c, p), cross(c, a, p));
+                    if ccw {
+                        d1 >= 0.0 && d2 >= 0.0 && d3 >= 0.0
+                    } else {
+                        d1 <= 0.0 && d2 <= 0.0 && d3 <= 0.0
+                    }
+                })
+        });
+
+        match ear {
+            Some(i) => {
+                let (ia, ic) = (remaining[(i + n - 1) % n], remaining[(i + 1) % n]);
+                triangles.push([points[ia], points[remaining[i]], points[ic]]);
+                remaining.remove(i);
+            }
+            // self-intersecting or degenerate; draw what we've got
+            None => return triangles,
+        }
+    }
+
+    if remaining.len() == 3 {
+        triangles.push([
+            points[remaining[0]],
+            points[remaining[1]],
+            points[remaining[2]],
+        ]);
+    }
+
+    triangles
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -73,6 +264,7 @@ pub struct Robot {
     pub id: u32,
     pub pos: RobotPosition,
     pub color: Color,
+    pub carrying: Option<Object>,
 }
 
 impl Robot {
@@ -81,6 +273,7 @@ impl Robot {
             id: id,
             pos: pos,
             color: color,
+            carrying: None,
         }
     }
 }
@@ -91,9 +284,12 @@ pub struct Environment {
     pub height_m: f32,
     pub obstacles: Vec<Obstacle>,
     pub walls: Vec<Wall>,
+    pub polygons: Vec<Polygon>,
     pub objects: Vec<Object>,
+    pub goals: Vec<Goal>,
     pub robots: HashMap<u32, Robot>,
     pub obstacle_readings: HashMap<u32, Vec<Vec3d<f32>>>,
+    shape_index: SpatialIndex,
 }
 
 impl Environment {
@@ -103,24 +299,164 @@ impl Environment {
             height_m: height_m,
             obstacles: vec![],
             walls: vec![],
+            polygons: vec![],
             objects: vec![],
+            goals: vec![],
             robots: HashMap::new(),
             obstacle_readings: HashMap::new(),
+            shape_index: SpatialIndex::new(width_m, height_m, SpatialIndex::DEFAULT_CELL_SIZE),
         }
     }
 
     pub fn add_obstacle(&mut self, obstacle: Obstacle) {
+        let (min, max) = obstacle.bounds();
+        self.shape_index
+            .insert(ShapeRef::Obstacle(self.obstacles.len()), min, max);
         self.obstacles.push(obstacle);
     }
 
     pub fn add_wall(&mut self, wall: Wall) {
+        let (min, max) = wall.bounds();
+        self.shape_index
+            .insert(ShapeRef::Wall(self.walls.len()), min, max);
         self.walls.push(wall);
     }
 
+    pub fn add_polygon(&mut self, polygon: Polygon) {
+        let (min, max) = polygon.bounds();
+        self.shape_index
+            .insert(ShapeRef::Polygon(self.polygons.len()), min, max);
+        self.polygons.push(polygon);
+    }
+
+    /// The add_* functions keep the shape index up to date on their own, but
+    /// anything that moves or removes obstacles, walls or polygons directly
+    /// has to call this afterward.
+    pub fn rebuild_shape_index(&mut self) {
+        self.shape_index.clear();
+        for (idx, obstacle) in self.obstacles.iter().enumerate() {
+            let (min, max) = obstacle.bounds();
+            self.shape_index.insert(ShapeRef::Obstacle(idx), min, max);
+        }
+        for (idx, wall) in self.walls.iter().enumerate() {
+            let (min, max) = wall.bounds();
+            self.shape_index.insert(ShapeRef::Wall(idx), min, max);
+        }
+        for (idx, polygon) in self.polygons.iter().enumerate() {
+            let (min, max) = polygon.bounds();
+            self.shape_index.insert(ShapeRef::Polygon(idx), min, max);
+        }
+    }
+
+    fn intersect_shape(&self, shape: ShapeRef, ray: &Ray<f32>) -> Option<Vec3d<f32>> {
+        match shape {
+            ShapeRef::Obstacle(idx) => self.obstacles[idx].intersect_with_ray(ray),
+            ShapeRef::Wall(idx) => self.walls[idx].intersect_with_ray(ray),
+            ShapeRef::Polygon(idx) => self.polygons[idx].intersect_with_ray(ray),
+        }
+    }
+
+    fn sweep_shape(&self, shape: ShapeRef, ray: &Ray<f32>, radius: f32) -> Option<SweepHit<f32>> {
+        match shape {
+            ShapeRef::Obstacle(idx) => self.obstacles[idx].sweep_circle(ray, radius),
+            ShapeRef::Wall(idx) => self.walls[idx].sweep_circle(ray, radius),
+            ShapeRef::Polygon(idx) => self.polygons[idx].sweep_circle(ray, radius),
+        }
+    }
+
+    /// Whether a circle of `radius` at the point would overlap any obstacle,
+    /// wall or polygon.
+    pub fn overlaps_shape(&self, point: Vec3d<f32>, radius: f32) -> bool {
+        let pad = Vec3d::new(radius, radius, 0.0);
+        self.shape_index
+            .query_box(point - pad, point + pad)
+            .into_iter()
+            .any(|shape| self.shape_overlaps(shape, point, radius))
+    }
+
+    fn shape_overlaps(&self, shape: ShapeRef, point: Vec3d<f32>, radius: f32) -> bool {
+        match shape {
+            ShapeRef::Obstacle(idx) => {
+                let obs = &self.obstacles[idx];
+                (Vec3d::new(obs.x, obs.y, 0.0) - point).len() < obs.radius + radius
+            }
+            ShapeRef::Wall(idx) => {
+                let wall = &self.walls[idx];
+                let start = Vec3d::new(wall.x1, wall.y1, 0.0);
+                let end = Vec3d::new(wall.x2, wall.y2, 0.0);
+                dist_to_segment(start, end, point) < radius
+            }
+            ShapeRef::Polygon(idx) => {
+                let points = &self.polygons[idx].points;
+                polygon_contains(points, point)
+                    || (0..points.len()).any(|i| {
+                        let end = points[(i + 1) % points.len()];
+                        dist_to_segment(points[i], end, point) < radius
+                    })
+            }
+        }
+    }
+
+    /// The nearest point where the ray hits an obstacle, wall or polygon, as
+    /// long as it's within `max_dist` of the ray's start.
+    pub fn closest_shape_hit(&self, ray: &Ray<f32>, max_dist: f32) -> Option<Vec3d<f32>> {
+        let mut closest: Option<(f32, Vec3d<f32>)> = None;
+        let mut tested = HashSet::new();
+        let mut test_shape = |shape: ShapeRef, closest: &mut Option<(f32, Vec3d<f32>)>| {
+            if !tested.insert(shape) {
+                return;
+            }
+            if let Some(hit) = self.intersect_shape(shape, ray) {
+                let dist = (hit - ray.from_loc).len();
+                if closest.is_none_or(|(closest_dist, _)| dist < closest_dist) {
+                    *closest = Some((dist, hit));
+                }
+            }
+        };
+
+        for shape in self.shape_index.overflow() {
+            test_shape(*shape, &mut closest);
+        }
+
+        // cells come nearest first, so once a hit is inside the cell being
+        // looked at, nothing further along can beat it
+        let dir = ray.to_loc - ray.from_loc;
+        self.shape_index
+            .walk_ray(ray.from_loc, dir, max_dist, |shapes, cell_exit| {
+                for shape in shapes {
+                    test_shape(*shape, &mut closest);
+                }
+                closest.is_some_and(|(dist, _)| dist <= cell_exit)
+            });
+
+        closest
+            .filter(|(dist, _)| *dist <= max_dist)
+            .map(|(_, hit)| hit)
+    }
+
+    /// The first obstacle, wall or polygon a circle of `radius` runs into
+    /// moving along the ray.
+    pub fn first_shape_collision(&self, ray: &Ray<f32>, radius: f32) -> Option<SweepHit<f32>> {
+        let pad = Vec3d::new(radius, radius, 0.0);
+        let (from, to) = (ray.from_loc, ray.to_loc);
+        let min = Vec3d::new(from.x.min(to.x), from.y.min(to.y), 0.0) - pad;
+        let max = Vec3d::new(from.x.max(to.x), from.y.max(to.y), 0.0) + pad;
+
+        self.shape_index
+            .query_box(min, max)
+            .into_iter()
+            .filter_map(|shape| self.sweep_shape(shape, ray, radius))
+            .reduce(|accum, hit| if accum.t <= hit.t { accum } else { hit })
+    }
+
     pub fn add_object(&mut self, object: Object) {
         self.objects.push(object);
     }
 
+    pub fn add_goal(&mut self, goal: Goal) {
+        self.goals.push(goal);
+    }
+
     pub fn add_robot(&mut self, robot: Robot) {
         self.robots.insert(robot.id, robot);
     }
@@ -137,6 +473,13 @@ impl Environment {
         }
     }
 
+    pub fn set_robot_carrying(&mut self, id: u32, carrying: Option<Object>) {
+        match self.robots.get_mut(&id) {
+            Some(robot) => robot.carrying = carrying,
+            None => println!("Error: tried to update an unregistered robot: {}", id),
+        }
+    }
+
     pub fn set_obstacle_readings(&mut self, id: u32, readings: Vec<Vec3d<f32>>) {
         if self.robots.contains_key(&id) {
             self.obstacle_readings.insert(id, readings);
@@ -149,17 +492,27 @@ pub struct EnvironmentScale {
     pub pix_per_meter: f32,
     pub env_width_m: f32,
     pub env_height_m: f32,
+    // where the environment's top-left corner lands in the window, in pixels
+    pub offset_x_pix: f32,
+    pub offset_y_pix: f32,
 }
 
 impl EnvironmentScale {
+    const MIN_PIX_PER_METER: f32 = 0.5;
+    const MAX_PIX_PER_METER: f32 = 1000.0;
+
     pub fn new(pix_per_meter: f32, env_width_m: f32, env_height_m: f32) -> Self {
         Self {
             pix_per_meter: pix_per_meter,
             env_width_m: env_width_m,
             env_height_m: env_height_m,
+            offset_x_pix: 0.0,
+            offset_y_pix: 0.0,
         }
     }
 
+    /// The largest scale that fits the whole environment in a window of the
+    /// given size, centered in it.
     pub fn from_env_size(
         width_pix: f32,
         height_pix: f32,
@@ -168,11 +521,15 @@ impl EnvironmentScale {
     ) -> Self {
         let pix_per_meter_width = width_pix / env_width_m;
         let pix_per_meter_height = height_pix / env_height_m;
-        Self {
-            pix_per_meter: pix_per_meter_width.min(pix_per_meter_height),
-            env_width_m: env_width_m,
-            env_height_m: env_height_m,
-        }
+        let mut scale = EnvironmentScale::new(
+            pix_per_meter_width.min(pix_per_meter_height),
+            env_width_m,
+            env_height_m,
+        );
+        let (env_width_pix, env_height_pix) = scale.env_size_in_pixels();
+        scale.offset_x_pix = (width_pix - env_width_pix) / 2.0;
+        scale.offset_y_pix = (height_pix - env_height_pix) / 2.0;
+        scale
     }
 
     pub fn dist_meters_to_pixels(&self, d: f32) -> f32 {
@@ -182,9 +539,29 @@ impl EnvironmentScale {
     pub fn coords_meters_to_pixels(&self, x: f32, y: f32) -> (f32, f32) {
         let x = self.dist_meters_to_pixels(x);
         let y = self.dist_meters_to_pixels(self.env_height_m) - self.dist_meters_to_pixels(y);
+        (x + self.offset_x_pix, y + self.offset_y_pix)
+    }
+
+    pub fn coords_pixels_to_meters(&self, x: f32, y: f32) -> (f32, f32) {
+        let x = (x - self.offset_x_pix) / self.pix_per_meter;
+        let y = self.env_height_m - (y - self.offset_y_pix) / self.pix_per_meter;
         (x, y)
     }
 
+    pub fn pan(&mut self, dx_pix: f32, dy_pix: f32) {
+        self.offset_x_pix += dx_pix;
+        self.offset_y_pix += dy_pix;
+    }
+
+    /// Zooms by `factor`, keeping whatever is under the given window position
+    /// where it is.
+    pub fn zoom_about(&mut self, factor: f32, x_pix: f32, y_pix: f32) {
+        let (x, y) = self.coords_pixels_to_meters(x_pix, y_pix);
+        self.zoom(factor);
+        let (new_x_pix, new_y_pix) = self.coords_meters_to_pixels(x, y);
+        self.pan(x_pix - new_x_pix, y_pix - new_y_pix);
+    }
+
     pub fn env_size_in_pixels(&self) -> (f32, f32) {
         let x = self.pix_per_meter * self.env_width_m;
         let y = self.pix_per_meter * self.env_height_m;
@@ -192,10 +569,109 @@ impl EnvironmentScale {
     }
 
     pub fn zoom_in(&mut self) {
-        self.pix_per_meter *= 2.0;
+        self.zoom(2.0);
     }
 
     pub fn zoom_out(&mut self) {
-        self.pix_per_meter /= 2.0;
+        self.zoom(0.5);
+    }
+
+    fn zoom(&mut self, factor: f32) {
+        self.pix_per_meter = (self.pix_per_meter * factor).clamp(
+            EnvironmentScale::MIN_PIX_PER_METER,
+            EnvironmentScale::MAX_PIX_PER_METER,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_pan_and_zoom() {
+        // a 50x50 environment in a wide window is centered side to side
+        let mut scale = EnvironmentScale::from_env_size(1200.0, 1000.0, 50.0, 50.0);
+        assert_eq!(scale.pix_per_meter, 20.0);
+        assert_eq!(scale.coords_meters_to_pixels(0.0, 50.0), (100.0, 0.0));
+        assert_eq!(scale.coords_meters_to_pixels(50.0, 0.0), (1100.0, 1000.0));
+
+        scale.pan(10.0, -20.0);
+        assert_eq!(scale.coords_meters_to_pixels(0.0, 50.0), (110.0, -20.0));
+        assert_eq!(scale.coords_pixels_to_meters(110.0, -20.0), (0.0, 50.0));
+
+        // the point under the cursor stays put
+        let (x, y) = scale.coords_pixels_to_meters(300.0, 400.0);
+        scale.zoom_about(2.0, 300.0, 400.0);
+        assert_eq!(scale.pix_per_meter, 40.0);
+        let (x_pix, y_pix) = scale.coords_meters_to_pixels(x, y);
+        assert!((x_pix - 300.0).abs() < 0.001 && (y_pix - 400.0).abs() < 0.001);
+
+        scale.zoom_about(1.0e6, 0.0, 0.0);
+        assert_eq!(scale.pix_per_meter, 1000.0);
+        scale.zoom_in();
+        assert_eq!(scale.pix_per_meter, 1000.0);
+
+        for _ in 0..20 {
+            scale.zoom_out();
+        }
+        assert_eq!(scale.pix_per_meter, 0.5);
+    }
+
+    #[test]
+    fn test_goal_contains() {
+        let circle = GoalShape::Circle {
+            x: 5.0,
+            y: 5.0,
+            radius: 1.0,
+        };
+        let goal = Goal::new("circle", circle, Color::RED, false);
+        assert!(goal.contains(Vec3d::new(5.5, 5.5, 0.0)));
+        assert!(!goal.contains(Vec3d::new(6.0, 6.0, 0.0)));
+
+        // an L shape, so the notch is outside even though it's inside the bounding box
+        let points = vec![
+            Vec3d::new(0.0, 0.0, 0.0),
+            Vec3d::new(4.0, 0.0, 0.0),
+            Vec3d::new(4.0, 2.0, 0.0),
+            Vec3d::new(2.0, 2.0, 0.0),
+            Vec3d::new(2.0, 4.0, 0.0),
+            Vec3d::new(0.0, 4.0, 0.0),
+        ];
+        let goal = Goal::new("ell", GoalShape::Polygon(points), Color::RED, false);
+        assert!(goal.contains(Vec3d::new(1.0, 3.0, 0.0)));
+        assert!(goal.contains(Vec3d::new(3.0, 1.0, 0.0)));
+        assert!(!goal.contains(Vec3d::new(3.0, 3.0, 0.0)));
+        assert!(!goal.contains(Vec3d::new(-1.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_triangulate_concave_polygon() {
+        let points = vec![
+            Vec3d::new(0.0, 0.0, 0.0),
+            Vec3d::new(4.0, 0.0, 0.0),
+            Vec3d::new(4.0, 2.0, 0.0),
+            Vec3d::new(2.0, 2.0, 0.0),
+            Vec3d::new(2.0, 4.0, 0.0),
+            Vec3d::new(0.0, 4.0, 0.0),
+        ];
+
+        // same answer whichever way the points wind
+        for points in [points.clone(), points.into_iter().rev().collect()] {
+            let triangles = triangulate_polygon(&points);
+            assert_eq!(triangles.len(), 4);
+
+            // none of the triangles spill into the notch, and together they cover the L
+            for [a, b, c] in &triangles {
+                assert!(polygon_contains(&points, (*a + *b + *c) / 3.0));
+            }
+            let area: f32 = triangles
+                .iter()
+                .map(|[a, b, c]| {
+                    ((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)).abs() / 2.0
+                })
+                .sum();
+            assert!((area - 12.0).abs() < 0.0001);
+        }
     }
 }
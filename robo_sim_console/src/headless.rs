@@ -0,0 +1,161 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use robo_sim_utils::robot_position::*;
+use robo_sim_utils::TO_DEG_F32;
+
+use super::console_comms::ConsoleComms;
+use super::environment::Environment;
+use super::launcher::RobotLauncher;
+use super::recording::FileRecorder;
+use super::sim_clock::SimClock;
+use super::simulator::Simulator;
+
+pub struct HeadlessOptions {
+    // in simulated time
+    pub duration: Option<Duration>,
+    pub summary_path: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StopReason {
+    AllRobotsDied,
+    DurationElapsed,
+}
+
+pub struct RunSummary {
+    pub stop_reason: StopReason,
+    pub elapsed: Duration,
+    pub sim_time_s: f64,
+    pub num_msgs: u64,
+    pub last_positions: BTreeMap<u32, RobotPosition>,
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "stop_reason: {:?}", self.stop_reason)?;
+        writeln!(f, "elapsed_s: {:.3}", self.elapsed.as_secs_f64())?;
+        writeln!(f, "sim_time_s: {:.3}", self.sim_time_s)?;
+        writeln!(f, "messages: {}", self.num_msgs)?;
+        for (id, pos) in &self.last_positions {
+            writeln!(
+                f,
+                "robot {}: x={:.3} y={:.3} heading_deg={:.1}",
+                id,
+                pos.location.x,
+                pos.location.y,
+                pos.heading_rad * TO_DEG_F32
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs the same message loop as the windowed console, but paced by its own
+/// clock instead of by draw events.
+pub fn run(
+    simulator: &mut Simulator,
+    clock: &mut SimClock,
+    comms: &mut ConsoleComms,
+    env: &mut Environment,
+    launcher: &mut RobotLauncher,
+    recorder: &mut Option<FileRecorder>,
+    options: &HeadlessOptions,
+) -> RunSummary {
+    const TICK: Duration = Duration::from_millis(10);
+    const KILL_GRACE: Duration = Duration::from_secs(2);
+    // a launched robot that hasn't registered by then isn't going to
+    const REGISTER_DEADLINE: Duration = Duration::from_secs(10);
+
+    let start = Instant::now();
+    let mut num_msgs = 0u64;
+    let mut last_positions = BTreeMap::new();
+    let mut kill_sent_at: Option<Instant> = None;
+    // the launched robots the run is still waiting on, whether to register
+    // or to die
+    let mut alive: BTreeSet<u32> = launcher.robot_ids().into_iter().collect();
+    let mut registered = BTreeSet::new();
+    let launched_any = !alive.is_empty();
+
+    let stop_reason = loop {
+        let msgs = comms.check_for_msgs();
+        num_msgs += msgs.len() as u64;
+        super::handle_msgs(&msgs, simulator, env, comms);
+
+        // a robot whose process is gone won't be sending RobotDying
+        for id in launcher.reap() {
+            if simulator.get_robot_pos(id).is_some() {
+                comms.unregister_robot(id);
+                simulator.robot_dying(id, env);
+            }
+            alive.remove(&id);
+        }
+
+        simulator.advance(clock, env, comms);
+        super::record_step(recorder, simulator, env, comms);
+
+        for robot in env.robots.values() {
+            last_positions.insert(robot.id, robot.pos);
+            registered.insert(robot.id);
+        }
+        alive.retain(|id| {
+            if registered.contains(id) {
+                env.robots.contains_key(id)
+            } else {
+                start.elapsed() < REGISTER_DEADLINE
+            }
+        });
+
+        // with nothing launched, only the duration ends the run
+        if kill_sent_at.is_none()
+            && alive.is_empty()
+            && (launched_any || options.duration.is_none())
+        {
+            break StopReason::AllRobotsDied;
+        }
+
+        if let Some(kill_sent_at) = kill_sent_at {
+            if env.robots.is_empty() || kill_sent_at.elapsed() >= KILL_GRACE {
+                break StopReason::DurationElapsed;
+            }
+        } else if let Some(duration) = options.duration {
+            if simulator.sim_time() >= duration.as_secs_f64() {
+                // give the robots a chance to shut down cleanly before the
+                // launcher kills whatever is left
+                comms.send_kill_all_msg();
+                kill_sent_at = Some(Instant::now());
+            }
+        }
+
+        if !clock.is_as_fast_as_possible() {
+            std::thread::sleep(TICK);
+        }
+    };
+
+    if let Some(recorder) = recorder {
+        if let Err(err) = recorder.flush() {
+            eprintln!("could not finish writing the recording: {}", err);
+        }
+    }
+
+    let summary = RunSummary {
+        stop_reason: stop_reason,
+        elapsed: start.elapsed(),
+        sim_time_s: simulator.sim_time(),
+        num_msgs: num_msgs,
+        last_positions: last_positions,
+    };
+
+    print!("{}", summary);
+    if let Some(path) = &options.summary_path {
+        if let Err(err) = fs::write(path, summary.to_string()) {
+            eprintln!("could not write summary to '{}': {}", path.display(), err);
+        }
+    }
+
+    summary
+}
@@ -0,0 +1,131 @@
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+use robo_sim_utils::TO_DEG_F32;
+
+use super::scenario::RobotSpawn;
+
+const ROBOT_EXEC_STEM: &str = "robo_sim_robot";
+
+/// Starts robot processes and owns them for the life of the console. Any
+/// children still running when the launcher is dropped get killed and reaped.
+pub struct RobotLauncher {
+    robot_exec: PathBuf,
+    log_dir: PathBuf,
+    children: Vec<(u32, Child)>,
+}
+
+impl RobotLauncher {
+    pub fn new(log_dir: &Path) -> io::Result<Self> {
+        let robot_exec = RobotLauncher::find_robot_exec()?;
+        fs::create_dir_all(log_dir)?;
+
+        Ok(Self {
+            robot_exec: robot_exec,
+            log_dir: log_dir.to_path_buf(),
+            children: vec![],
+        })
+    }
+
+    // the robot binary is built into the same directory as the console's
+    fn find_robot_exec() -> io::Result<PathBuf> {
+        let console_exec = env::current_exe()?;
+        let exec_dir = console_exec.parent().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no parent dir for '{}'", console_exec.display()),
+            )
+        })?;
+
+        let robot_exec = exec_dir.join(format!("{}{}", ROBOT_EXEC_STEM, env::consts::EXE_SUFFIX));
+        if !robot_exec.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("robot executable not found at '{}'", robot_exec.display()),
+            ));
+        }
+
+        Ok(robot_exec)
+    }
+
+    pub fn start_robot(&mut self, spawn: &RobotSpawn, seed: u64) -> io::Result<()> {
+        let stdout_log = File::create(self.log_path(spawn.id, "stdout"))?;
+        let stderr_log = File::create(self.log_path(spawn.id, "stderr"))?;
+
+        let child = Command::new(&self.robot_exec)
+            .args(&[
+                "-i", &spawn.id.to_string(),
+                "-x", &spawn.pos.location.x.to_string(),
+                "-y", &spawn.pos.location.y.to_string(),
+                "-d", &(spawn.pos.heading_rad * TO_DEG_F32).to_string(),
+                "-c", &spawn.color.to_hex_string(),
+                "-v", &spawn.max_vel.to_string(),
+                "-a", &spawn.max_angular_vel_deg.to_string(),
+                "-r", &spawn.radius.to_string(),
+                "--sensor-rays", &spawn.sensor.num_rays.to_string(),
+                "--sensor-fov", &(spawn.sensor.fov_rad * TO_DEG_F32).to_string(),
+                "--sensor-range", &spawn.sensor.max_range.to_string(),
+                "--sensor-offset", &(spawn.sensor.angle_offset_rad * TO_DEG_F32).to_string(),
+                "--sensor-noise", &spawn.sensor.range_noise_std_dev.to_string(),
+                "--sensor-dropout", &spawn.sensor.dropout_prob.to_string(),
+                "--seed", &seed.to_string(),
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::from(stdout_log))
+            .stderr(Stdio::from(stderr_log))
+            .spawn()?;
+
+        println!("started robot {} as pid {}", spawn.id, child.id());
+        self.children.push((spawn.id, child));
+
+        Ok(())
+    }
+
+    fn log_path(&self, id: u32, stream: &str) -> PathBuf {
+        self.log_dir.join(format!("robot_{}.{}.log", id, stream))
+    }
+
+    pub fn robot_ids(&self) -> Vec<u32> {
+        self.children.iter().map(|(id, _)| *id).collect()
+    }
+
+    /// Collects any children that have exited on their own so they don't
+    /// linger as zombies, and returns their robot IDs.
+    pub fn reap(&mut self) -> Vec<u32> {
+        let mut exited = vec![];
+        self.children.retain_mut(|(id, child)| match child.try_wait() {
+            Ok(Some(status)) => {
+                println!("robot {} process exited: {}", id, status);
+                exited.push(*id);
+                false
+            }
+            Ok(None) => true,
+            Err(err) => {
+                println!("Error: could not check on robot {} process: {}", id, err);
+                true
+            }
+        });
+        exited
+    }
+
+    pub fn kill_all(&mut self) {
+        for (id, child) in self.children.iter_mut() {
+            if let Ok(None) = child.try_wait() {
+                if let Err(err) = child.kill() {
+                    println!("Error: could not kill robot {} process: {}", id, err);
+                }
+            }
+            let _ = child.wait();
+        }
+        self.children.clear();
+    }
+}
+
+impl Drop for RobotLauncher {
+    fn drop(&mut self) {
+        self.kill_all();
+    }
+}
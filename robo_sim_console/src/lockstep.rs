@@ -0,0 +1,175 @@
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
+/// Bookkeeping for synchronous stepping: each tick is sent to every robot,
+/// and the simulator only steps once they've all answered that they're done
+/// sending commands for it (or the timeout runs out).
+pub struct Lockstep {
+    tick: u64,
+    timeout: Duration,
+    // the first tick waits for this many robots, so every run starts with
+    // the same robots at the same simulated time, unless some of them still
+    // haven't registered when the timeout runs out
+    num_robots: usize,
+    registering_since: Option<Instant>,
+    waiting_on: BTreeSet<u32>,
+    started_at: Option<Instant>,
+}
+
+impl Lockstep {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+    pub fn new(timeout: Duration, num_robots: usize) -> Self {
+        Self {
+            tick: 0,
+            timeout: timeout,
+            num_robots: num_robots.max(1),
+            registering_since: None,
+            waiting_on: BTreeSet::new(),
+            started_at: None,
+        }
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn can_start_tick(&mut self, num_registered: usize) -> bool {
+        if self.tick > 0 || num_registered >= self.num_robots {
+            return true;
+        }
+
+        let since = *self.registering_since.get_or_insert_with(Instant::now);
+        if since.elapsed() < self.timeout {
+            return false;
+        }
+        println!(
+            "starting with {} of {} robots registered",
+            num_registered, self.num_robots
+        );
+        true
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    pub fn start_tick(&mut self, ids: impl Iterator<Item = u32>) {
+        self.waiting_on = ids.collect();
+        self.started_at = Some(Instant::now());
+    }
+
+    /// Restarts the current tick's timeout (or the first tick's wait for
+    /// robots to register), so the time spent paused doesn't count against
+    /// the robots.
+    pub fn hold(&mut self) {
+        if self.started_at.is_some() {
+            self.started_at = Some(Instant::now());
+        }
+        if self.registering_since.is_some() {
+            self.registering_since = Some(Instant::now());
+        }
+    }
+
+    pub fn robot_done(&mut self, id: u32, tick: u64) {
+        // anything for an earlier tick is a straggler from a timed-out one
+        if tick == self.tick {
+            self.waiting_on.remove(&id);
+        }
+    }
+
+    pub fn robot_gone(&mut self, id: u32) {
+        self.waiting_on.remove(&id);
+    }
+
+    /// Returns true, and moves on to the next tick, once every robot is done
+    /// with the current one or the timeout has run out.
+    pub fn try_finish(&mut self) -> bool {
+        let started_at = match self.started_at {
+            Some(started_at) => started_at,
+            None => return false,
+        };
+
+        if !self.waiting_on.is_empty() {
+            if started_at.elapsed() < self.timeout {
+                return false;
+            }
+            println!(
+                "tick {} timed out waiting on robots {:?}",
+                self.tick, self.waiting_on
+            );
+        }
+
+        self.waiting_on.clear();
+        self.started_at = None;
+        self.tick += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waits_for_every_robot() {
+        let mut lockstep = Lockstep::new(Duration::from_secs(60), 3);
+        assert!(!lockstep.try_finish());
+        assert!(!lockstep.can_start_tick(2));
+        assert!(lockstep.can_start_tick(3));
+
+        lockstep.start_tick(vec![1, 2, 3].into_iter());
+        lockstep.robot_done(1, 0);
+        assert!(!lockstep.try_finish());
+
+        // a late answer to an old tick doesn't count
+        lockstep.robot_done(2, 7);
+        lockstep.robot_gone(3);
+        assert!(!lockstep.try_finish());
+
+        lockstep.robot_done(2, 0);
+        assert!(lockstep.try_finish());
+        assert_eq!(lockstep.tick(), 1);
+        assert!(!lockstep.is_waiting());
+
+        // once it's going, robots dying off doesn't hold it up
+        assert!(lockstep.can_start_tick(0));
+    }
+
+    #[test]
+    fn test_times_out() {
+        let mut lockstep = Lockstep::new(Duration::from_millis(0), 1);
+        lockstep.start_tick(vec![1].into_iter());
+        assert!(lockstep.try_finish());
+        assert_eq!(lockstep.tick(), 1);
+    }
+
+    #[test]
+    fn test_first_tick_times_out_on_missing_robots() {
+        let mut lockstep = Lockstep::new(Duration::from_millis(50), 3);
+        assert!(!lockstep.can_start_tick(2));
+
+        // the third robot never registers
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(lockstep.can_start_tick(2));
+
+        lockstep.start_tick(vec![1, 2].into_iter());
+        lockstep.robot_done(1, 0);
+        lockstep.robot_done(2, 0);
+        assert!(lockstep.try_finish());
+        assert_eq!(lockstep.tick(), 1);
+    }
+
+    #[test]
+    fn test_hold() {
+        let mut lockstep = Lockstep::new(Duration::from_millis(50), 1);
+        lockstep.hold();
+        assert!(!lockstep.is_waiting());
+
+        lockstep.start_tick(vec![1].into_iter());
+        std::thread::sleep(Duration::from_millis(60));
+        lockstep.hold();
+        assert!(!lockstep.try_finish());
+        assert_eq!(lockstep.tick(), 0);
+    }
+}
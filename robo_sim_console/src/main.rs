@@ -1,40 +1,41 @@
+extern crate getopts;
 extern crate piston_window;
 
 pub mod console_comms;
 pub mod display;
+pub mod editor;
 pub mod environment;
+pub mod headless;
+pub mod launcher;
+pub mod lockstep;
+pub mod recording;
+pub mod replay;
+pub mod scenario;
+pub mod sim_clock;
 pub mod simulator;
+pub mod spatial_index;
 
 use std::env;
-use std::process::Command;
+use std::path::PathBuf;
+use std::time::Duration;
 
-use robo_sim_utils::color::*;
+use getopts::Options;
+
+use robo_sim_utils::comms::MsgHeader;
 use robo_sim_utils::messages::*;
 
 use environment as environ;
 use piston_window as pw;
 
-fn start_robot(id: u32) {
-    let exec_name = match env::consts::OS {
-        "windows" => "robo_sim_robot.exe",
-        _ => panic!("Unsupported OS: {}", env::consts::OS),
-    };
-
-    Command::new(&exec_name)
-        .args(&[
-            "-i", &id.to_string(), "-x", "1", "-y", "1", "-c", "blue", "-v", "1", "-a", "20",
-        ])
-        .spawn()
-        .expect("failed to execute process");
-}
-
+// returns true once the last registered robot has died
 fn handle_msgs(
-    msgs: &Vec<Box<dyn Message>>,
+    msgs: &Vec<(MsgHeader, Box<dyn Message>)>,
     simulator: &mut simulator::Simulator,
     env: &mut environ::Environment,
-    comms: &console_comms::ConsoleComms,
-) {
-    for msg in msgs {
+    comms: &mut console_comms::ConsoleComms,
+) -> bool {
+    let mut all_robots_gone = false;
+    for (header, msg) in msgs {
         match msg.get_type() {
             MessageType::Alive => {
                 handle_alive_msg(msg, simulator, env, comms);
@@ -43,23 +44,76 @@ fn handle_msgs(
                 handle_position_msg(msg, simulator, env);
             }
             MessageType::RequestPosition => {
-                handle_request_position_msg(msg, simulator, comms);
+                handle_request_position_msg(msg, header.seq, simulator, comms);
             }
             MessageType::GetObstacles => {
-                handle_get_obstacles_msg(msg, simulator, env, comms);
+                handle_get_obstacles_msg(msg, header.seq, simulator, env, comms);
             }
             MessageType::RobotDying => {
-                println!("got msg type: {:?}", msg.get_type());
+                all_robots_gone = handle_robot_dying_msg(msg, simulator, env, comms);
             }
             MessageType::Move => {
-                handle_move_msg(msg, simulator, env);
+                handle_move_msg(msg, simulator);
             }
             MessageType::Spin => {
-                println!("got msg type: {:?}", msg.get_type());
+                handle_spin_msg(msg, simulator);
+            }
+            MessageType::GetObjects => {
+                handle_get_objects_msg(msg, header.seq, simulator, env, comms);
+            }
+            MessageType::Pickup => {
+                handle_pickup_msg(msg, header.seq, simulator, env, comms);
+            }
+            MessageType::Drop => {
+                handle_drop_msg(msg, header.seq, simulator, env, comms);
+            }
+            MessageType::SensorConfig => {
+                handle_sensor_config_msg(msg, simulator);
+            }
+            MessageType::TickDone => {
+                handle_tick_done_msg(msg, simulator);
             }
             _ => println!("got unsupported msg type: {:?}", msg.get_type()),
         }
     }
+
+    send_goal_events(simulator, env, comms);
+
+    all_robots_gone
+}
+
+fn send_goal_events(
+    simulator: &mut simulator::Simulator,
+    env: &environ::Environment,
+    comms: &console_comms::ConsoleComms,
+) {
+    for event in simulator.take_goal_events() {
+        let notify = env.goals[event.goal_id as usize].notify;
+        if notify {
+            let elapsed_s = event.elapsed.as_secs_f32();
+            comms.send_goal_event_msg(event.robot_id, event.goal_id, event.entered, elapsed_s);
+        }
+    }
+}
+
+// writes out this pass through the console loop, and stops recording if the
+// file can't be written to
+fn record_step(
+    recorder: &mut Option<recording::FileRecorder>,
+    simulator: &simulator::Simulator,
+    env: &environ::Environment,
+    comms: &mut console_comms::ConsoleComms,
+) {
+    let result = match recorder.as_mut() {
+        Some(recorder) => recorder.record(simulator.sim_time(), env, comms.take_traffic()),
+        None => return,
+    };
+
+    if let Err(err) = result {
+        println!("Error: could not write to the recording, so stopping it: {}", err);
+        *recorder = None;
+        comms.set_recording(false);
+    }
 }
 
 fn handle_alive_msg(
@@ -93,6 +147,7 @@ fn handle_position_msg(
 
 fn handle_request_position_msg(
     msg: &Box<dyn Message>,
+    request_seq: u32,
     simulator: &mut simulator::Simulator,
     comms: &console_comms::ConsoleComms,
 ) {
@@ -103,77 +158,319 @@ fn handle_request_position_msg(
         return;
     }
 
-    comms.send_position_msg(msg.id, pos.unwrap());
+    comms.send_position_msg(msg.id, pos.unwrap(), simulator.sim_time(), Some(request_seq));
 }
 
 fn handle_get_obstacles_msg(
     msg: &Box<dyn Message>,
+    request_seq: u32,
     simulator: &mut simulator::Simulator,
     env: &mut environ::Environment,
     comms: &console_comms::ConsoleComms,
 ) {
     let msg = downcast::<GetObstaclesMsg>(msg, "get_obstacles");
     let obs_readings = simulator.get_obs_readings(msg.id, env);
-    comms.send_obs_readings_msg(msg.id, obs_readings);
+    comms.send_obs_readings_msg(msg.id, obs_readings, request_seq);
+}
+
+fn handle_get_objects_msg(
+    msg: &Box<dyn Message>,
+    request_seq: u32,
+    simulator: &mut simulator::Simulator,
+    env: &mut environ::Environment,
+    comms: &console_comms::ConsoleComms,
+) {
+    let msg = downcast::<GetObjectsMsg>(msg, "get_objects");
+    let object_readings = simulator.get_object_readings(msg.id, msg.max_range, msg.fov_rad, env);
+    comms.send_object_readings_msg(msg.id, object_readings, request_seq);
 }
 
-fn handle_move_msg(
+fn handle_pickup_msg(
     msg: &Box<dyn Message>,
+    request_seq: u32,
     simulator: &mut simulator::Simulator,
     env: &mut environ::Environment,
+    comms: &console_comms::ConsoleComms,
 ) {
+    let msg = downcast::<PickupMsg>(msg, "pickup");
+    let carrying = simulator.pickup_object(msg.id, env);
+    comms.send_carry_status_msg(msg.id, carrying, request_seq);
+}
+
+fn handle_drop_msg(
+    msg: &Box<dyn Message>,
+    request_seq: u32,
+    simulator: &mut simulator::Simulator,
+    env: &mut environ::Environment,
+    comms: &console_comms::ConsoleComms,
+) {
+    let msg = downcast::<DropMsg>(msg, "drop");
+    let carrying = simulator.drop_object(msg.id, env);
+    comms.send_carry_status_msg(msg.id, carrying, request_seq);
+}
+
+fn handle_robot_dying_msg(
+    msg: &Box<dyn Message>,
+    simulator: &mut simulator::Simulator,
+    env: &mut environ::Environment,
+    comms: &mut console_comms::ConsoleComms,
+) -> bool {
+    let msg = downcast::<RobotDyingMsg>(msg, "robot_dying");
+    println!("robot {} is dying", msg.id);
+    comms.unregister_robot(msg.id);
+    simulator.robot_dying(msg.id, env)
+}
+
+fn key_to_robot_id(key: pw::Key) -> Option<u32> {
+    match key {
+        pw::Key::D1 => Some(1),
+        pw::Key::D2 => Some(2),
+        pw::Key::D3 => Some(3),
+        pw::Key::D4 => Some(4),
+        pw::Key::D5 => Some(5),
+        pw::Key::D6 => Some(6),
+        pw::Key::D7 => Some(7),
+        pw::Key::D8 => Some(8),
+        pw::Key::D9 => Some(9),
+        _ => None,
+    }
+}
+
+fn handle_move_msg(msg: &Box<dyn Message>, simulator: &mut simulator::Simulator) {
     let msg = downcast::<MoveMsg>(msg, "move");
-    simulator.move_robot(msg.id, msg.x, msg.y, env);
+    simulator.set_velocity_cmd(msg.id, msg.x, msg.y);
+}
+
+fn handle_spin_msg(msg: &Box<dyn Message>, simulator: &mut simulator::Simulator) {
+    let msg = downcast::<SpinMsg>(msg, "spin");
+    simulator.queue_spin(msg.id, msg.theta);
+}
+
+fn handle_sensor_config_msg(msg: &Box<dyn Message>, simulator: &mut simulator::Simulator) {
+    let msg = downcast::<SensorConfigMsg>(msg, "sensor_config");
+    simulator.set_sensor_config(msg.id, msg.config);
+}
+
+fn handle_tick_done_msg(msg: &Box<dyn Message>, simulator: &mut simulator::Simulator) {
+    let msg = downcast::<TickDoneMsg>(msg, "tick_done");
+    simulator.tick_done(msg.id, msg.tick);
 }
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let mut opts = Options::new();
+    opts.optopt("s", "scenario", "scenario file to load", "PATH");
+    opts.optopt("", "log-dir", "directory for robot output logs", "PATH");
+    opts.optflag("", "headless", "run without a window");
+    opts.optflag("", "slide", "let robots slide along whatever they collide with");
+    opts.optopt(
+        "",
+        "speed",
+        "simulated seconds per real second, or 'max' to run as fast as possible (default 1)",
+        "FACTOR",
+    );
+    opts.optflag("", "lockstep", "step only once every robot has sent its commands");
+    opts.optopt(
+        "",
+        "tick-timeout",
+        "in lockstep mode, how long to wait on slow robots (default 1000)",
+        "MS",
+    );
+    opts.optopt("", "seed", "seed for all randomness, overriding the scenario's", "SEED");
+    opts.optopt("", "duration", "stop a headless run after this much simulated time", "SECONDS");
+    opts.optopt("", "summary", "write a headless run summary to this file", "PATH");
+    opts.optopt("", "record", "record the run to this file", "PATH");
+    opts.optopt("", "replay", "play back a recorded run instead of running one", "PATH");
+    opts.optopt(
+        "",
+        "save-scenario",
+        "where edit mode saves the scenario (default scenario.toml)",
+        "PATH",
+    );
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            panic!("{}", f.to_string())
+        }
+    };
+
+    if let Some(path) = matches.opt_str("replay") {
+        match recording::Recording::from_file(&path) {
+            Ok(recording) => replay::run(recording),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let scenario = match matches.opt_str("s") {
+        Some(path) => match scenario::Scenario::from_file(&path) {
+            Ok(scenario) => scenario,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        },
+        None => scenario::Scenario::default_scenario(),
+    };
+
+    // with no robots to die, nothing would ever end the run
+    if matches.opt_present("headless")
+        && !matches.opt_present("duration")
+        && scenario.robots.is_empty()
+    {
+        eprintln!("a headless run of a scenario with no robots needs a --duration");
+        std::process::exit(1);
+    }
+
+    let seed = match matches.opt_str("seed") {
+        Some(seed_opt) => seed_opt.parse::<u64>().unwrap(), // ok to panic
+        None => scenario.seed.unwrap_or_else(rand::random),
+    };
+    println!("using seed {}", seed);
+
     let mut simulator = simulator::Simulator::new(0.2f32);
+    simulator.set_seed(seed);
+    simulator.set_wall_sliding(matches.opt_present("slide"));
+
+    let real_time_factor = match matches.opt_str("speed").as_deref() {
+        Some("max") => None,
+        Some(factor) => Some(factor.parse::<f32>().unwrap()), // ok to panic
+        None => Some(1.0),
+    };
+    let mut clock = sim_clock::SimClock::new(simulator.time_step(), real_time_factor);
+
+    if matches.opt_present("lockstep") {
+        let timeout = match matches.opt_str("tick-timeout") {
+            Some(timeout_opt) => Duration::from_millis(timeout_opt.parse::<u64>().unwrap()), // ok to panic
+            None => lockstep::Lockstep::DEFAULT_TIMEOUT,
+        };
+        let lockstep = lockstep::Lockstep::new(timeout, scenario.robots.len());
+        simulator.set_lockstep(Some(lockstep));
+    }
     let mut comms = console_comms::ConsoleComms::new();
     comms.open().expect("could not open comms");
 
-    const ENV_WIDTH_M: f32 = 50.0;
-    const ENV_HEIGHT_M: f32 = 50.0;
-    const ENV_SCALE_PIX_PER_METER: f32 = 20.0;
+    let mut env = scenario.env;
 
-    let mut env = environ::Environment::new(ENV_WIDTH_M, ENV_HEIGHT_M);
-    env.add_obstacle(environ::Obstacle::new(10.0, 10.0, 2.0));
-    env.add_obstacle(environ::Obstacle::new(15.0, 15.0, 3.0));
-    env.add_wall(environ::Wall::new(15.0, 35.0, 25.0, 35.0));
-    env.add_wall(environ::Wall::new(25.0, 35.0, 35.0, 25.0));
-    env.add_wall(environ::Wall::new(35.0, 25.0, 35.0, 15.0));
-    env.add_object(environ::Object::new(49.0, 49.0, 1.0, Color::RED));
+    let mut recorder = match matches.opt_str("record") {
+        Some(path) => match recording::Recorder::create(&path, &env) {
+            Ok(recorder) => {
+                comms.set_recording(true);
+                Some(recorder)
+            }
+            Err(err) => {
+                eprintln!("could not create recording '{}': {}", path, err);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
 
-    // env.add_wall(environ::Wall::new(15.0, 15.0, 15.0, 35.0));
-    // env.add_wall(environ::Wall::new(15.0, 35.0, 35.0, 35.0));
-    // env.add_wall(environ::Wall::new(35.0, 35.0, 35.0, 15.0));
-    // env.add_wall(environ::Wall::new(35.0, 15.0, 15.0, 15.0));
+    let log_dir = matches
+        .opt_str("log-dir")
+        .unwrap_or_else(|| String::from("robot_logs"));
+    let mut launcher = match launcher::RobotLauncher::new(log_dir.as_ref()) {
+        Ok(launcher) => launcher,
+        Err(err) => {
+            eprintln!("could not set up robot launcher: {}", err);
+            std::process::exit(1);
+        }
+    };
 
-    let scale = environ::EnvironmentScale::new(ENV_SCALE_PIX_PER_METER, env.width_m, env.height_m);
+    for spawn in &scenario.robots {
+        let robot_seed = robo_sim_utils::derive_seed(seed, spawn.id);
+        if let Err(err) = launcher.start_robot(spawn, robot_seed) {
+            eprintln!("could not start robot {}: {}", spawn.id, err);
+        }
+    }
 
-    start_robot(1);
+    if matches.opt_present("headless") {
+        let duration = matches.opt_str("duration").map(|duration_opt| {
+            Duration::from_secs_f64(duration_opt.parse::<f64>().unwrap()) // ok to panic
+        });
+        let options = headless::HeadlessOptions {
+            duration: duration,
+            summary_path: matches.opt_str("summary").map(PathBuf::from),
+        };
+        headless::run(
+            &mut simulator,
+            &mut clock,
+            &mut comms,
+            &mut env,
+            &mut launcher,
+            &mut recorder,
+            &options,
+        );
+        return;
+    }
+
+    const WINDOW_SIZE: [f64; 2] = [1200.0, 1000.0];
 
-    let mut window: pw::PistonWindow = pw::WindowSettings::new("RoboSim", [1200, 1000])
+    let mut window: pw::PistonWindow = pw::WindowSettings::new("RoboSim", WINDOW_SIZE)
         .exit_on_esc(true)
         .build()
         .unwrap();
+    let mut view = display::ViewControl::new(WINDOW_SIZE);
+    let mut scale = view.fitted_scale(&env);
+    let mut layers = display::Layers::new();
+    let mut editor = editor::Editor::new();
+    let save_path = matches
+        .opt_str("save-scenario")
+        .unwrap_or_else(|| String::from("scenario.toml"));
+    println!("e: edit mode");
     while let Some(event) = window.next() {
+        view.handle_event(&event, &mut scale);
+        editor.handle_event(&event, &scale, &mut env, &mut simulator, &comms);
 
         if let pw::Event::Input(pw::Input::Button(btn_args), _) = event {
             if btn_args.state == pw::ButtonState::Press {
                 if let pw::Button::Keyboard(key) = btn_args.button {
                     match key {
-                        pw::Key::P => { comms.send_pause_msg(); },
-                        _ => {},
+                        pw::Key::P => {
+                            comms.send_pause_msg();
+                            clock.toggle_paused();
+                        },
+                        pw::Key::K => { comms.send_kill_all_msg(); },
+                        pw::Key::E => {
+                            editor.toggle_active();
+                            view.set_pan_with_left(!editor.is_active());
+                        },
+                        pw::Key::Tab if editor.is_active() => { editor.next_tool(); },
+                        pw::Key::S if editor.is_active() => {
+                            let edited = editor::edited_scenario(&env, &scenario.robots, seed);
+                            match edited.save(&save_path) {
+                                Ok(()) => println!("saved scenario to {}", save_path),
+                                Err(err) => println!("Error: {}", err),
+                            }
+                        },
+                        _ if layers.toggle_for_key(key) => {},
+                        _ => {
+                            // number keys kill the robot with that ID
+                            if let Some(id) = key_to_robot_id(key) {
+                                comms.send_kill_msg(id);
+                            }
+                        },
                     }
                 }
             }
         }
 
+        launcher.reap();
+
         window.draw_2d(&event, |context, graphics, _device| {
             let msgs = comms.check_for_msgs();
             handle_msgs(&msgs, &mut simulator, &mut env, &mut comms);
+            simulator.advance(&mut clock, &mut env, &comms);
+            record_step(&mut recorder, &simulator, &env, &mut comms);
 
-            display::draw_env(&env, &scale, context, graphics);
+            display::draw_env(&env, &scale, &mut layers, context, graphics);
+            if let Some(wall) = editor.pending_wall(&scale) {
+                display::draw_pending_wall(&wall, &scale, context, graphics);
+            }
         });
     }
 }
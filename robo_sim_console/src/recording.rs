@@ -0,0 +1,569 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use robo_sim_utils::color::Color;
+use robo_sim_utils::comms::{WireField, WireReader};
+use robo_sim_utils::robot_position::RobotPosition;
+use robo_sim_utils::vec3d::Vec3d;
+
+use super::environment::*;
+
+// A recording is the magic bytes and format version, the environment as the
+// run started, then a stream of entries. Each entry is a kind byte and the
+// simulated time it happened at, followed by either a frame (every robot,
+// object and set of obstacle readings), a message as it went over the wire,
+// or the obstacles and walls after they were edited. Fields are encoded just
+// as they are in messages.
+const MAGIC: &[u8; 4] = b"RSIM";
+// version 2 has the protocol header on recorded messages, and version 3 has
+// edits to the obstacles and walls
+const VERSION: u8 = 3;
+
+const FRAME_ENTRY: u8 = 1;
+const INBOUND_MSG_ENTRY: u8 = 2;
+const OUTBOUND_MSG_ENTRY: u8 = 3;
+const SHAPES_ENTRY: u8 = 4;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MsgDirection {
+    Inbound,
+    Outbound,
+}
+
+/// A message the console sent or received. `id` is the robot on the other
+/// end, or 0 if the message came from an address no robot has registered.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RecordedMsg {
+    pub direction: MsgDirection,
+    pub id: u32,
+    pub bytes: Vec<u8>,
+}
+
+impl RecordedMsg {
+    pub fn new(direction: MsgDirection, id: u32, bytes: &[u8]) -> Self {
+        Self {
+            direction: direction,
+            id: id,
+            bytes: bytes.to_vec(),
+        }
+    }
+}
+
+/// Everything about the environment that can change while the simulation
+/// runs, as of one moment in simulated time.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Frame {
+    pub sim_time: f64,
+    pub robots: Vec<Robot>,
+    pub objects: Vec<Object>,
+    pub obstacle_readings: Vec<(u32, Vec<Vec3d<f32>>)>,
+}
+
+impl Frame {
+    pub fn capture(sim_time: f64, env: &Environment) -> Self {
+        // sorted, so the same run always makes the same file
+        let mut robots: Vec<Robot> = env.robots.values().copied().collect();
+        robots.sort_by_key(|robot| robot.id);
+
+        let mut obstacle_readings: Vec<(u32, Vec<Vec3d<f32>>)> = env
+            .obstacle_readings
+            .iter()
+            .map(|(id, readings)| (*id, readings.clone()))
+            .collect();
+        obstacle_readings.sort_by_key(|(id, _)| *id);
+
+        Self {
+            sim_time: sim_time,
+            robots: robots,
+            objects: env.objects.clone(),
+            obstacle_readings: obstacle_readings,
+        }
+    }
+
+    pub fn apply(&self, env: &mut Environment) {
+        env.robots = self.robots.iter().map(|robot| (robot.id, *robot)).collect();
+        env.objects = self.objects.clone();
+        env.obstacle_readings = self.obstacle_readings.iter().cloned().collect();
+    }
+}
+
+/// The obstacles and walls from some moment on. They only change when
+/// they're edited during the run.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Shapes {
+    pub sim_time: f64,
+    pub obstacles: Vec<Obstacle>,
+    pub walls: Vec<Wall>,
+}
+
+impl Shapes {
+    pub fn capture(sim_time: f64, env: &Environment) -> Self {
+        Self {
+            sim_time: sim_time,
+            obstacles: env.obstacles.clone(),
+            walls: env.walls.clone(),
+        }
+    }
+
+    pub fn apply(&self, env: &mut Environment) {
+        // rebuilding the index every frame would be a waste
+        if env.obstacles != self.obstacles || env.walls != self.walls {
+            env.obstacles = self.obstacles.clone();
+            env.walls = self.walls.clone();
+            env.rebuild_shape_index();
+        }
+    }
+}
+
+pub type FileRecorder = Recorder<BufWriter<File>>;
+
+/// Writes a recording as the run goes.
+pub struct Recorder<W: Write> {
+    out: W,
+    last_frame_time: Option<f64>,
+    // as last written, to spot edits
+    shapes: Shapes,
+}
+
+impl Recorder<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, env: &Environment) -> io::Result<Self> {
+        Recorder::new(BufWriter::new(File::create(path)?), env)
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(out: W, env: &Environment) -> io::Result<Self> {
+        let mut recorder = Self {
+            out: out,
+            last_frame_time: None,
+            shapes: Shapes::capture(0.0, env),
+        };
+
+        let mut buf = vec![];
+        buf.extend_from_slice(MAGIC);
+        buf.push(VERSION);
+        pack_static_env(&mut buf, env);
+        recorder.out.write_all(&buf)?;
+
+        Ok(recorder)
+    }
+
+    /// Writes out the messages since the last call, the obstacles and walls if
+    /// they've been edited, then a frame if simulated time has moved on since
+    /// the last one.
+    pub fn record(
+        &mut self,
+        sim_time: f64,
+        env: &Environment,
+        msgs: Vec<RecordedMsg>,
+    ) -> io::Result<()> {
+        let mut buf = vec![];
+        for msg in msgs {
+            buf.push(match msg.direction {
+                MsgDirection::Inbound => INBOUND_MSG_ENTRY,
+                MsgDirection::Outbound => OUTBOUND_MSG_ENTRY,
+            });
+            sim_time.encode(&mut buf);
+            msg.id.encode(&mut buf);
+            (msg.bytes.len() as u32).encode(&mut buf);
+            buf.extend_from_slice(&msg.bytes);
+        }
+
+        if env.obstacles != self.shapes.obstacles || env.walls != self.shapes.walls {
+            self.shapes = Shapes::capture(sim_time, env);
+            buf.push(SHAPES_ENTRY);
+            sim_time.encode(&mut buf);
+            put_shapes(&mut buf, &self.shapes);
+        }
+
+        if self.last_frame_time != Some(sim_time) {
+            buf.push(FRAME_ENTRY);
+            sim_time.encode(&mut buf);
+            pack_frame(&mut buf, &Frame::capture(sim_time, env));
+            self.last_frame_time = Some(sim_time);
+        }
+
+        self.out.write_all(&buf)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// A whole recording read back in.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Recording {
+    // the environment as the run started, minus anything frames fill in
+    pub env: Environment,
+    pub frames: Vec<Frame>,
+    pub msgs: Vec<(f64, RecordedMsg)>,
+    // starting with the ones in env, and then after each edit
+    pub shapes: Vec<Shapes>,
+}
+
+impl Recording {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = fs::read(path)
+            .map_err(|err| format!("could not read recording '{}': {}", path.display(), err))?;
+        Recording::parse(&contents)
+            .map_err(|err| format!("invalid recording '{}': {}", path.display(), err).into())
+    }
+
+    pub fn parse(buf: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut reader = WireReader::new(buf, "recording");
+        if reader.take::<4>()? != *MAGIC {
+            return Err("not a recording")?;
+        }
+        let version = u8::decode(&mut reader)?;
+        if version != VERSION {
+            return Err(format!("unsupported recording version {}", version))?;
+        }
+
+        let env = parse_static_env(&mut reader)?;
+
+        let mut frames = vec![];
+        let mut msgs = vec![];
+        let mut shapes = vec![Shapes::capture(0.0, &env)];
+        while !reader.is_empty() {
+            // a run that was killed can leave a partly written entry at the
+            // end; everything before it is still good
+            let entry_start = reader.offset();
+            if let Err(err) = parse_entry(&mut reader, &mut frames, &mut msgs, &mut shapes) {
+                println!(
+                    "Error: ignoring the end of the recording from byte {}: {}",
+                    entry_start, err
+                );
+                break;
+            }
+        }
+
+        Ok(Self {
+            env: env,
+            frames: frames,
+            msgs: msgs,
+            shapes: shapes,
+        })
+    }
+
+    pub fn duration_s(&self) -> f64 {
+        self.frames.last().map_or(0.0, |frame| frame.sim_time)
+    }
+}
+
+fn parse_entry(
+    reader: &mut WireReader,
+    frames: &mut Vec<Frame>,
+    msgs: &mut Vec<(f64, RecordedMsg)>,
+    shapes: &mut Vec<Shapes>,
+) -> Result<(), Box<dyn Error>> {
+    let kind = u8::decode(reader)?;
+    let sim_time = f64::decode(reader)?;
+    match kind {
+        FRAME_ENTRY => frames.push(parse_frame(reader, sim_time)?),
+        INBOUND_MSG_ENTRY | OUTBOUND_MSG_ENTRY => {
+            let direction = if kind == INBOUND_MSG_ENTRY {
+                MsgDirection::Inbound
+            } else {
+                MsgDirection::Outbound
+            };
+            let id = u32::decode(reader)?;
+            let len = u32::decode(reader)? as usize;
+            let bytes = reader.take_slice(len)?;
+            msgs.push((sim_time, RecordedMsg::new(direction, id, bytes)));
+        }
+        SHAPES_ENTRY => shapes.push(parse_shapes(reader, sim_time)?),
+        _ => return Err(format!("unknown entry kind {}", kind))?,
+    }
+
+    Ok(())
+}
+
+fn pack_static_env(buf: &mut Vec<u8>, env: &Environment) {
+    env.width_m.encode(buf);
+    env.height_m.encode(buf);
+    put_shapes(buf, &Shapes::capture(0.0, env));
+
+    (env.polygons.len() as u32).encode(buf);
+    for polygon in &env.polygons {
+        put_points(buf, &polygon.points);
+    }
+
+    (env.goals.len() as u32).encode(buf);
+    for goal in &env.goals {
+        (goal.name.len() as u32).encode(buf);
+        buf.extend_from_slice(goal.name.as_bytes());
+        goal.color.encode(buf);
+        goal.notify.encode(buf);
+        match &goal.shape {
+            GoalShape::Circle { x, y, radius } => {
+                0u8.encode(buf);
+                x.encode(buf);
+                y.encode(buf);
+                radius.encode(buf);
+            }
+            GoalShape::Polygon(points) => {
+                1u8.encode(buf);
+                put_points(buf, points);
+            }
+        }
+    }
+}
+
+fn parse_static_env(reader: &mut WireReader) -> Result<Environment, Box<dyn Error>> {
+    let mut env = Environment::new(f32::decode(reader)?, f32::decode(reader)?);
+    parse_shapes(reader, 0.0)?.apply(&mut env);
+
+    for _ in 0..u32::decode(reader)? {
+        env.add_polygon(Polygon::new(parse_points(reader)?));
+    }
+
+    for _ in 0..u32::decode(reader)? {
+        let name_len = u32::decode(reader)? as usize;
+        let name = String::from_utf8(reader.take_slice(name_len)?.to_vec())?;
+        let color = Color::decode(reader)?;
+        let notify = bool::decode(reader)?;
+        let shape = match u8::decode(reader)? {
+            0 => GoalShape::Circle {
+                x: f32::decode(reader)?,
+                y: f32::decode(reader)?,
+                radius: f32::decode(reader)?,
+            },
+            1 => GoalShape::Polygon(parse_points(reader)?),
+            shape => return Err(format!("unknown goal shape {}", shape))?,
+        };
+        env.add_goal(Goal::new(&name, shape, color, notify));
+    }
+
+    Ok(env)
+}
+
+// just the obstacles and walls; the time goes in the entry
+fn put_shapes(buf: &mut Vec<u8>, shapes: &Shapes) {
+    (shapes.obstacles.len() as u32).encode(buf);
+    for obs in &shapes.obstacles {
+        obs.x.encode(buf);
+        obs.y.encode(buf);
+        obs.radius.encode(buf);
+    }
+
+    (shapes.walls.len() as u32).encode(buf);
+    for wall in &shapes.walls {
+        wall.x1.encode(buf);
+        wall.y1.encode(buf);
+        wall.x2.encode(buf);
+        wall.y2.encode(buf);
+    }
+}
+
+fn parse_shapes(reader: &mut WireReader, sim_time: f64) -> Result<Shapes, Box<dyn Error>> {
+    let mut obstacles = vec![];
+    for _ in 0..u32::decode(reader)? {
+        let (x, y, radius) = (
+            f32::decode(reader)?,
+            f32::decode(reader)?,
+            f32::decode(reader)?,
+        );
+        obstacles.push(Obstacle::new(x, y, radius));
+    }
+
+    let mut walls = vec![];
+    for _ in 0..u32::decode(reader)? {
+        let (x1, y1) = (f32::decode(reader)?, f32::decode(reader)?);
+        let (x2, y2) = (f32::decode(reader)?, f32::decode(reader)?);
+        walls.push(Wall::new(x1, y1, x2, y2));
+    }
+
+    Ok(Shapes {
+        sim_time: sim_time,
+        obstacles: obstacles,
+        walls: walls,
+    })
+}
+
+fn pack_frame(buf: &mut Vec<u8>, frame: &Frame) {
+    (frame.robots.len() as u32).encode(buf);
+    for robot in &frame.robots {
+        robot.id.encode(buf);
+        robot.pos.encode(buf);
+        robot.color.encode(buf);
+        match &robot.carrying {
+            Some(object) => {
+                true.encode(buf);
+                put_object(buf, object);
+            }
+            None => false.encode(buf),
+        }
+    }
+
+    (frame.objects.len() as u32).encode(buf);
+    for object in &frame.objects {
+        put_object(buf, object);
+    }
+
+    (frame.obstacle_readings.len() as u32).encode(buf);
+    for (id, readings) in &frame.obstacle_readings {
+        id.encode(buf);
+        put_points(buf, readings);
+    }
+}
+
+fn parse_frame(reader: &mut WireReader, sim_time: f64) -> Result<Frame, Box<dyn Error>> {
+    let mut robots = vec![];
+    for _ in 0..u32::decode(reader)? {
+        let id = u32::decode(reader)?;
+        let pos = RobotPosition::decode(reader)?;
+        let mut robot = Robot::new(id, pos, Color::decode(reader)?);
+        if bool::decode(reader)? {
+            robot.carrying = Some(parse_object(reader)?);
+        }
+        robots.push(robot);
+    }
+
+    let mut objects = vec![];
+    for _ in 0..u32::decode(reader)? {
+        objects.push(parse_object(reader)?);
+    }
+
+    let mut obstacle_readings = vec![];
+    for _ in 0..u32::decode(reader)? {
+        let id = u32::decode(reader)?;
+        obstacle_readings.push((id, parse_points(reader)?));
+    }
+
+    Ok(Frame {
+        sim_time: sim_time,
+        robots: robots,
+        objects: objects,
+        obstacle_readings: obstacle_readings,
+    })
+}
+
+fn put_object(buf: &mut Vec<u8>, object: &Object) {
+    object.x.encode(buf);
+    object.y.encode(buf);
+    object.radius.encode(buf);
+    object.color.encode(buf);
+}
+
+fn parse_object(reader: &mut WireReader) -> Result<Object, Box<dyn Error>> {
+    let (x, y, radius) = (
+        f32::decode(reader)?,
+        f32::decode(reader)?,
+        f32::decode(reader)?,
+    );
+    Ok(Object::new(x, y, radius, Color::decode(reader)?))
+}
+
+// only x and y; everything in the environment is flat
+fn put_points(buf: &mut Vec<u8>, points: &[Vec3d<f32>]) {
+    (points.len() as u32).encode(buf);
+    for p in points {
+        p.x.encode(buf);
+        p.y.encode(buf);
+    }
+}
+
+fn parse_points(reader: &mut WireReader) -> Result<Vec<Vec3d<f32>>, Box<dyn Error>> {
+    let mut points = vec![];
+    for _ in 0..u32::decode(reader)? {
+        points.push(Vec3d::new(f32::decode(reader)?, f32::decode(reader)?, 0.0));
+    }
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::scenario::Scenario;
+
+    #[test]
+    fn test_round_trip() {
+        let mut env = Scenario::default_scenario().env;
+        env.add_polygon(Polygon::new(vec![
+            Vec3d::new(1.0, 1.0, 0.0),
+            Vec3d::new(2.0, 1.0, 0.0),
+            Vec3d::new(1.0, 2.0, 0.0),
+        ]));
+
+        let mut buf = vec![];
+        let mut recorder = Recorder::new(&mut buf, &env).unwrap();
+
+        let pos = RobotPosition::new(Vec3d::new(3.0, 4.0, 0.0), 1.0);
+        env.add_robot(Robot::new(1, pos, Color::BLUE));
+        env.set_obstacle_readings(1, vec![Vec3d::new(5.0, 6.0, 0.0)]);
+        let alive = RecordedMsg::new(MsgDirection::Inbound, 1, &[1, 2, 3]);
+        recorder.record(0.0, &env, vec![alive.clone()]).unwrap();
+
+        // no frame unless time has moved on
+        let start = RecordedMsg::new(MsgDirection::Outbound, 1, &[4]);
+        recorder.record(0.0, &env, vec![start.clone()]).unwrap();
+
+        let object = env.objects.remove(0);
+        env.set_robot_carrying(1, Some(object));
+        recorder.record(0.2, &env, vec![]).unwrap();
+
+        let recording = Recording::parse(&buf).unwrap();
+        assert_eq!(recording.env.obstacles, env.obstacles);
+        assert_eq!(recording.env.walls, env.walls);
+        assert_eq!(recording.env.polygons, env.polygons);
+        assert_eq!(recording.env.goals, env.goals);
+        assert!(recording.env.robots.is_empty());
+        assert_eq!(recording.msgs, vec![(0.0, alive), (0.0, start)]);
+        assert_eq!(recording.duration_s(), 0.2);
+
+        assert_eq!(recording.frames.len(), 2);
+        assert_eq!(recording.frames[0].objects.len(), 1);
+        assert_eq!(recording.frames[0].obstacle_readings[0].1[0].y, 6.0);
+
+        let mut replayed = recording.env.clone();
+        recording.frames[1].apply(&mut replayed);
+        assert_eq!(replayed, env);
+    }
+
+    #[test]
+    fn test_edited_shapes() {
+        let mut env = Environment::new(10.0, 10.0);
+        env.add_wall(Wall::new(1.0, 1.0, 1.0, 9.0));
+        let mut buf = vec![];
+        let mut recorder = Recorder::new(&mut buf, &env).unwrap();
+        recorder.record(0.0, &env, vec![]).unwrap();
+
+        env.add_obstacle(Obstacle::new(5.0, 5.0, 1.0));
+        env.walls.clear();
+        env.rebuild_shape_index();
+        recorder.record(0.2, &env, vec![]).unwrap();
+        recorder.record(0.4, &env, vec![]).unwrap();
+
+        // only the edit gets an entry
+        let recording = Recording::parse(&buf).unwrap();
+        assert_eq!(recording.shapes.len(), 2);
+        assert_eq!(recording.shapes[1].sim_time, 0.2);
+
+        let mut replayed = recording.env.clone();
+        assert_eq!(replayed.walls.len(), 1);
+        recording.shapes[1].apply(&mut replayed);
+        assert_eq!(replayed, env);
+        recording.shapes[0].apply(&mut replayed);
+        assert_eq!(replayed, recording.env);
+    }
+
+    #[test]
+    fn test_truncated_recording() {
+        let env = Environment::new(10.0, 10.0);
+        let mut buf = vec![];
+        let mut recorder = Recorder::new(&mut buf, &env).unwrap();
+        recorder.record(0.0, &env, vec![]).unwrap();
+        recorder.record(0.2, &env, vec![]).unwrap();
+
+        // a cut-off last entry is dropped, but a cut-off header is an error
+        let recording = Recording::parse(&buf[..buf.len() - 3]).unwrap();
+        assert_eq!(recording.frames.len(), 1);
+        assert!(Recording::parse(&buf[..6]).is_err());
+        assert!(Recording::parse(b"nope").is_err());
+    }
+}
@@ -0,0 +1,300 @@
+use std::time::{Duration, Instant};
+
+use piston_window as pw;
+use pw::AdvancedWindow;
+
+use robo_sim_utils::comms;
+
+use super::display;
+use super::recording::{Frame, MsgDirection, RecordedMsg, Recording, Shapes};
+
+/// Where playback of a recording is up to, in simulated time.
+pub struct Replay {
+    recording: Recording,
+    position_s: f64,
+    speed: f64,
+    playing: bool,
+}
+
+impl Replay {
+    const SEEK_STEP_S: f64 = 5.0;
+    const MIN_SPEED: f64 = 1.0 / 16.0;
+    const MAX_SPEED: f64 = 64.0;
+
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording: recording,
+            position_s: 0.0,
+            speed: 1.0,
+            playing: true,
+        }
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    pub fn position_s(&self) -> f64 {
+        self.position_s
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// The latest frame at or before the current position.
+    pub fn current_frame(&self) -> Option<&Frame> {
+        let frames = &self.recording.frames;
+        let idx = frames.partition_point(|frame| frame.sim_time <= self.position_s);
+        if idx == 0 {
+            frames.first()
+        } else {
+            Some(&frames[idx - 1])
+        }
+    }
+
+    /// The obstacles and walls as they were at the current position, after
+    /// any edits up to then.
+    pub fn current_shapes(&self) -> &Shapes {
+        let shapes = &self.recording.shapes;
+        let idx = shapes.partition_point(|shapes| shapes.sim_time <= self.position_s);
+        &shapes[idx.max(1) - 1]
+    }
+
+    /// Moves playback along by however much real time has passed, and returns
+    /// the messages that went by on the way. Stops at the end.
+    pub fn advance(&mut self, real_elapsed: Duration) -> &[(f64, RecordedMsg)] {
+        if !self.playing {
+            return &[];
+        }
+
+        let old_position_s = self.position_s;
+        self.position_s += real_elapsed.as_secs_f64() * self.speed;
+        if self.position_s >= self.recording.duration_s() {
+            self.position_s = self.recording.duration_s();
+            self.playing = false;
+        }
+
+        let msgs = &self.recording.msgs;
+        let start = msgs.partition_point(|(sim_time, _)| *sim_time <= old_position_s);
+        let end = msgs.partition_point(|(sim_time, _)| *sim_time <= self.position_s);
+        // the very first messages are at time 0, which nothing is ever after
+        let start = if old_position_s == 0.0 { 0 } else { start };
+        &msgs[start..end]
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        !self.playing && self.position_s >= self.recording.duration_s()
+    }
+
+    pub fn toggle_playing(&mut self) {
+        // playing from the end starts over
+        if self.is_at_end() {
+            self.position_s = 0.0;
+        }
+        self.playing = !self.playing;
+    }
+
+    pub fn seek(&mut self, delta_s: f64) {
+        self.position_s = (self.position_s + delta_s)
+            .max(0.0)
+            .min(self.recording.duration_s());
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed * 2.0).min(Replay::MAX_SPEED);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = (self.speed / 2.0).max(Replay::MIN_SPEED);
+    }
+
+    pub fn restart(&mut self) {
+        self.position_s = 0.0;
+    }
+}
+
+fn print_msg(sim_time: f64, msg: &RecordedMsg) {
+    let arrow = match msg.direction {
+        MsgDirection::Inbound => "<-",
+        MsgDirection::Outbound => "->",
+    };
+    match comms::parse_message(&msg.bytes) {
+        Ok((_header, parsed)) => println!(
+            "{:8.2}s {} robot {}: {:?}",
+            sim_time,
+            arrow,
+            msg.id,
+            parsed.get_type()
+        ),
+        Err(err) => println!("{:8.2}s {} robot {}: {}", sim_time, arrow, msg.id, err),
+    }
+}
+
+/// Plays a recording back in a window. No robots or comms are involved;
+/// everything drawn comes from the recording.
+pub fn run(recording: Recording) {
+    const WINDOW_SIZE: [f64; 2] = [1200.0, 1000.0];
+
+    println!(
+        "replaying {:.1}s with {} frames and {} messages",
+        recording.duration_s(),
+        recording.frames.len(),
+        recording.msgs.len()
+    );
+    println!("space: play/pause, left/right: seek, up/down: speed, home: restart");
+    println!("t: trails, r: sensor rays, o: obstacle readings");
+    println!("drag: pan, scroll: zoom, f: fit to window");
+
+    let mut env = recording.env.clone();
+    let mut replay = Replay::new(recording);
+
+    let mut window: pw::PistonWindow = pw::WindowSettings::new("RoboSim replay", WINDOW_SIZE)
+        .exit_on_esc(true)
+        .build()
+        .unwrap();
+    let mut view = display::ViewControl::new(WINDOW_SIZE);
+    let mut scale = view.fitted_scale(&env);
+    let mut layers = display::Layers::new();
+    let mut title = String::new();
+    let mut last_draw = Instant::now();
+    while let Some(event) = window.next() {
+        view.handle_event(&event, &mut scale);
+
+        if let pw::Event::Input(pw::Input::Button(btn_args), _) = event {
+            if btn_args.state == pw::ButtonState::Press {
+                if let pw::Button::Keyboard(key) = btn_args.button {
+                    match key {
+                        // playing from the end starts over, so the trails
+                        // do too
+                        pw::Key::Space => {
+                            if replay.is_at_end() {
+                                layers.clear_trails();
+                            }
+                            replay.toggle_playing();
+                        }
+                        // jumping around would join up unrelated stretches
+                        // of the trails
+                        pw::Key::Left => {
+                            replay.seek(-Replay::SEEK_STEP_S);
+                            layers.clear_trails();
+                        }
+                        pw::Key::Right => {
+                            replay.seek(Replay::SEEK_STEP_S);
+                            layers.clear_trails();
+                        }
+                        pw::Key::Up => replay.faster(),
+                        pw::Key::Down => replay.slower(),
+                        pw::Key::Home => {
+                            replay.restart();
+                            layers.clear_trails();
+                        }
+                        _ => {
+                            layers.toggle_for_key(key);
+                        }
+                    }
+                }
+            }
+        }
+
+        let new_title = format!(
+            "RoboSim replay - {:.1}s / {:.1}s at {}x{}",
+            replay.position_s(),
+            replay.recording().duration_s(),
+            replay.speed(),
+            if replay.is_playing() { "" } else { " (paused)" }
+        );
+        if new_title != title {
+            window.set_title(new_title.clone());
+            title = new_title;
+        }
+
+        window.draw_2d(&event, |context, graphics, _device| {
+            let now = Instant::now();
+            for (sim_time, msg) in replay.advance(now - last_draw) {
+                print_msg(*sim_time, msg);
+            }
+            last_draw = now;
+
+            if let Some(frame) = replay.current_frame() {
+                frame.apply(&mut env);
+            }
+            replay.current_shapes().apply(&mut env);
+            display::draw_env(&env, &scale, &mut layers, context, graphics);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::environment as environ;
+    use super::super::recording::Recorder;
+
+    fn recording_with_frames(times: &[f64]) -> Recording {
+        let env = environ::Environment::new(10.0, 10.0);
+        let mut buf = vec![];
+        let mut recorder = Recorder::new(&mut buf, &env).unwrap();
+        for time in times {
+            let msg = RecordedMsg::new(MsgDirection::Outbound, 1, &[0]);
+            recorder.record(*time, &env, vec![msg]).unwrap();
+        }
+        Recording::parse(&buf).unwrap()
+    }
+
+    #[test]
+    fn test_playback() {
+        let mut replay = Replay::new(recording_with_frames(&[0.0, 1.0, 2.0, 3.0]));
+        assert_eq!(replay.current_frame().unwrap().sim_time, 0.0);
+
+        assert_eq!(replay.advance(Duration::from_millis(500)).len(), 1);
+        assert_eq!(replay.current_frame().unwrap().sim_time, 0.0);
+
+        replay.faster();
+        assert_eq!(replay.advance(Duration::from_millis(500)).len(), 1);
+        assert_eq!(replay.position_s(), 1.5);
+        assert_eq!(replay.current_frame().unwrap().sim_time, 1.0);
+
+        replay.seek(-10.0);
+        assert_eq!(replay.position_s(), 0.0);
+        replay.seek(2.0);
+        assert_eq!(replay.current_frame().unwrap().sim_time, 2.0);
+
+        // stops at the end, and playing again starts over
+        assert_eq!(replay.advance(Duration::from_secs(10)).len(), 1);
+        assert!(!replay.is_playing());
+        assert!(replay.is_at_end());
+        assert_eq!(replay.position_s(), 3.0);
+        replay.toggle_playing();
+        assert_eq!(replay.position_s(), 0.0);
+        assert!(!replay.is_at_end());
+
+        replay.toggle_playing();
+        assert!(replay.advance(Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn test_shapes_follow_edits() {
+        let mut env = environ::Environment::new(10.0, 10.0);
+        let mut buf = vec![];
+        let mut recorder = Recorder::new(&mut buf, &env).unwrap();
+        recorder.record(0.0, &env, vec![]).unwrap();
+        env.add_obstacle(environ::Obstacle::new(5.0, 5.0, 1.0));
+        recorder.record(1.0, &env, vec![]).unwrap();
+        recorder.record(2.0, &env, vec![]).unwrap();
+
+        let mut replay = Replay::new(Recording::parse(&buf).unwrap());
+        assert!(replay.current_shapes().obstacles.is_empty());
+        replay.seek(1.5);
+        assert_eq!(replay.current_shapes().obstacles.len(), 1);
+
+        // and seeking back to before the edit undoes it
+        replay.seek(-1.0);
+        assert!(replay.current_shapes().obstacles.is_empty());
+    }
+}
@@ -0,0 +1,748 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use robo_sim_utils::color::*;
+use robo_sim_utils::robot_position::*;
+use robo_sim_utils::sensor_config::*;
+use robo_sim_utils::vec3d::*;
+use robo_sim_utils::{TO_DEG_F32, TO_RAD_F32};
+
+use super::environment::*;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RobotSpawn {
+    pub id: u32,
+    pub pos: RobotPosition,
+    pub color: Color,
+    pub max_vel: f32,
+    pub max_angular_vel_deg: f32,
+    pub radius: f32,
+    pub sensor: SensorConfig,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Scenario {
+    pub env: Environment,
+    pub robots: Vec<RobotSpawn>,
+    // seeds the sensor noise and every robot's behaviors; None picks a fresh
+    // one each run
+    pub seed: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioDef {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    environment: EnvironmentDef,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    obstacles: Vec<ObstacleDef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    walls: Vec<WallDef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    polygons: Vec<PolygonDef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    objects: Vec<ObjectDef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    goals: Vec<GoalDef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    robots: Vec<RobotDef>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDef {
+    width: f32,
+    height: f32,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct ObstacleDef {
+    x: f32,
+    y: f32,
+    radius: f32,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct WallDef {
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct PolygonDef {
+    points: Vec<[f32; 2]>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct ObjectDef {
+    x: f32,
+    y: f32,
+    radius: f32,
+    color: String,
+}
+
+// a goal is either a circle (x, y and radius) or a polygon (points)
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct GoalDef {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    x: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    y: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    radius: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    points: Option<Vec<[f32; 2]>>,
+    #[serde(default = "GoalDef::default_color")]
+    color: String,
+    #[serde(default)]
+    notify: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct RobotDef {
+    id: u32,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    heading: f32,
+    #[serde(default = "RobotDef::default_color")]
+    color: String,
+    #[serde(default = "RobotDef::default_max_vel")]
+    max_vel: f32,
+    #[serde(default = "RobotDef::default_max_angular_vel")]
+    max_angular_vel: f32,
+    #[serde(default = "RobotDef::default_radius")]
+    radius: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sensor: Option<SensorDef>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct SensorDef {
+    num_rays: Option<u32>,
+    fov: Option<f32>,
+    range: Option<f32>,
+    offset: Option<f32>,
+    noise: Option<f32>,
+    dropout: Option<f32>,
+}
+
+impl GoalDef {
+    fn default_color() -> String {
+        String::from("#00ff0040")
+    }
+}
+
+impl RobotDef {
+    fn default_color() -> String {
+        String::from("black")
+    }
+
+    // what the console used to launch every robot with
+    fn default_max_vel() -> f32 {
+        1.0
+    }
+
+    fn default_max_angular_vel() -> f32 {
+        20.0
+    }
+
+    fn default_radius() -> f32 {
+        0.5
+    }
+}
+
+impl Scenario {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("could not read scenario '{}': {}", path.display(), err))?;
+        Scenario::from_toml_str(&contents)
+            .map_err(|err| format!("invalid scenario '{}': {}", path.display(), err).into())
+    }
+
+    pub fn from_toml_str(contents: &str) -> Result<Self, Box<dyn Error>> {
+        let def: ScenarioDef = toml::from_str(contents)?;
+        Scenario::from_def(def)
+    }
+
+    /// The map that used to be hard-coded into main(), used when no scenario
+    /// file is given.
+    pub fn default_scenario() -> Self {
+        let mut env = Environment::new(50.0, 50.0);
+        env.add_obstacle(Obstacle::new(10.0, 10.0, 2.0));
+        env.add_obstacle(Obstacle::new(15.0, 15.0, 3.0));
+        env.add_wall(Wall::new(15.0, 35.0, 25.0, 35.0));
+        env.add_wall(Wall::new(25.0, 35.0, 35.0, 25.0));
+        env.add_wall(Wall::new(35.0, 25.0, 35.0, 15.0));
+        env.add_object(Object::new(49.0, 49.0, 1.0, Color::RED));
+        let target = GoalShape::Circle {
+            x: 49.0,
+            y: 49.0,
+            radius: 2.0,
+        };
+        env.add_goal(Goal::new("red_object", target, Color::new(255, 0, 0, 64), true));
+
+        let robots = vec![RobotSpawn {
+            id: 1,
+            pos: RobotPosition::new(Vec3d::new(1.0, 1.0, 0.0), 0.0),
+            color: Color::BLUE,
+            max_vel: 1.0,
+            max_angular_vel_deg: 20.0,
+            radius: 0.5,
+            sensor: SensorConfig::default(),
+        }];
+
+        Self {
+            env: env,
+            robots: robots,
+            seed: None,
+        }
+    }
+
+    fn from_def(def: ScenarioDef) -> Result<Self, Box<dyn Error>> {
+        let (width, height) = (def.environment.width, def.environment.height);
+        if !is_positive(width) || !is_positive(height) {
+            return Err(format!(
+                "environment: width and height must be positive, got {} x {}",
+                width, height
+            ))?;
+        }
+
+        let in_bounds = |x: f32, y: f32| x >= 0.0 && x <= width && y >= 0.0 && y <= height;
+
+        let mut env = Environment::new(width, height);
+
+        for (i, obs) in def.obstacles.iter().enumerate() {
+            if !is_positive(obs.radius) {
+                return Err(format!(
+                    "obstacles[{}]: radius must be positive, got {}",
+                    i, obs.radius
+                ))?;
+            }
+            if !in_bounds(obs.x, obs.y) {
+                return Err(format!(
+                    "obstacles[{}]: center ({}, {}) is outside the environment",
+                    i, obs.x, obs.y
+                ))?;
+            }
+            env.add_obstacle(Obstacle::new(obs.x, obs.y, obs.radius));
+        }
+
+        for (i, wall) in def.walls.iter().enumerate() {
+            if !in_bounds(wall.x1, wall.y1) || !in_bounds(wall.x2, wall.y2) {
+                return Err(format!(
+                    "walls[{}]: endpoint outside the environment: ({}, {}) -> ({}, {})",
+                    i, wall.x1, wall.y1, wall.x2, wall.y2
+                ))?;
+            }
+            if wall.x1 == wall.x2 && wall.y1 == wall.y2 {
+                return Err(format!("walls[{}]: wall has zero length", i))?;
+            }
+            env.add_wall(Wall::new(wall.x1, wall.y1, wall.x2, wall.y2));
+        }
+
+        for (i, polygon) in def.polygons.iter().enumerate() {
+            let points = polygon_points_from_def(&polygon.points, "polygons", i)?;
+            if let Some(p) = points.iter().find(|p| !in_bounds(p.x, p.y)) {
+                return Err(format!(
+                    "polygons[{}]: point ({}, {}) is outside the environment",
+                    i, p.x, p.y
+                ))?;
+            }
+            env.add_polygon(Polygon::new(points));
+        }
+
+        for (i, object) in def.objects.iter().enumerate() {
+            if !is_positive(object.radius) {
+                return Err(format!(
+                    "objects[{}]: radius must be positive, got {}",
+                    i, object.radius
+                ))?;
+            }
+            if !in_bounds(object.x, object.y) {
+                return Err(format!(
+                    "objects[{}]: center ({}, {}) is outside the environment",
+                    i, object.x, object.y
+                ))?;
+            }
+            let color = parse_color(&object.color, "objects", i)?;
+            env.add_object(Object::new(object.x, object.y, object.radius, color));
+        }
+
+        for (i, goal) in def.goals.iter().enumerate() {
+            if goal.name.is_empty() {
+                return Err(format!("goals[{}]: name must not be empty", i))?;
+            }
+            let shape = goal_shape_from_def(goal, i)?;
+            let color = parse_color(&goal.color, "goals", i)?;
+            env.add_goal(Goal::new(&goal.name, shape, color, goal.notify));
+        }
+
+        let mut robots = Vec::with_capacity(def.robots.len());
+        let mut seen_ids = HashSet::new();
+        for (i, robot) in def.robots.iter().enumerate() {
+            if robot.id == 0 {
+                return Err(format!("robots[{}]: id must be at least 1", i))?;
+            }
+            if !seen_ids.insert(robot.id) {
+                return Err(format!("robots[{}]: duplicate robot id {}", i, robot.id))?;
+            }
+            if !in_bounds(robot.x, robot.y) {
+                return Err(format!(
+                    "robots[{}]: spawn ({}, {}) is outside the environment",
+                    i, robot.x, robot.y
+                ))?;
+            }
+            if !is_positive(robot.radius) {
+                return Err(format!(
+                    "robots[{}]: radius must be positive, got {}",
+                    i, robot.radius
+                ))?;
+            }
+            if robot.max_vel < 0.0 || robot.max_angular_vel < 0.0 {
+                return Err(format!(
+                    "robots[{}]: max_vel and max_angular_vel must not be negative",
+                    i
+                ))?;
+            }
+
+            let sensor = match &robot.sensor {
+                Some(sensor_def) => sensor_from_def(sensor_def, i)?,
+                None => SensorConfig::default(),
+            };
+
+            robots.push(RobotSpawn {
+                id: robot.id,
+                pos: RobotPosition::new(
+                    Vec3d::new(robot.x, robot.y, 0.0),
+                    robot.heading * TO_RAD_F32,
+                ),
+                color: parse_color(&robot.color, "robots", i)?,
+                max_vel: robot.max_vel,
+                max_angular_vel_deg: robot.max_angular_vel,
+                radius: robot.radius,
+                sensor: sensor,
+            });
+        }
+
+        Ok(Self {
+            env: env,
+            robots: robots,
+            seed: def.seed,
+        })
+    }
+
+    /// Writes the scenario out in the same format `from_file` reads. Robots
+    /// are written from their spawns; whatever's in `env.robots` is left out.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = self.to_toml_string()?;
+        fs::write(path, contents)
+            .map_err(|err| format!("could not write scenario '{}': {}", path.display(), err))?;
+        Ok(())
+    }
+
+    pub fn to_toml_string(&self) -> Result<String, Box<dyn Error>> {
+        Ok(toml::to_string(&self.to_def())?)
+    }
+
+    fn to_def(&self) -> ScenarioDef {
+        let env = &self.env;
+        let points_def = |points: &[Vec3d<f32>]| points.iter().map(|p| [p.x, p.y]).collect();
+
+        let goals = env
+            .goals
+            .iter()
+            .map(|goal| {
+                let (x, y, radius, points) = match &goal.shape {
+                    GoalShape::Circle { x, y, radius } => (Some(*x), Some(*y), Some(*radius), None),
+                    GoalShape::Polygon(points) => (None, None, None, Some(points_def(points))),
+                };
+                GoalDef {
+                    name: goal.name.clone(),
+                    x: x,
+                    y: y,
+                    radius: radius,
+                    points: points,
+                    color: goal.color.to_hex_string(),
+                    notify: goal.notify,
+                }
+            })
+            .collect();
+
+        let robots = self
+            .robots
+            .iter()
+            .map(|spawn| RobotDef {
+                id: spawn.id,
+                x: spawn.pos.location.x,
+                y: spawn.pos.location.y,
+                heading: spawn.pos.heading_rad * TO_DEG_F32,
+                color: spawn.color.to_hex_string(),
+                max_vel: spawn.max_vel,
+                max_angular_vel: spawn.max_angular_vel_deg,
+                radius: spawn.radius,
+                sensor: if spawn.sensor == SensorConfig::default() {
+                    None
+                } else {
+                    Some(SensorDef {
+                        num_rays: Some(spawn.sensor.num_rays),
+                        fov: Some(spawn.sensor.fov_rad * TO_DEG_F32),
+                        range: Some(spawn.sensor.max_range),
+                        offset: Some(spawn.sensor.angle_offset_rad * TO_DEG_F32),
+                        noise: Some(spawn.sensor.range_noise_std_dev),
+                        dropout: Some(spawn.sensor.dropout_prob),
+                    })
+                },
+            })
+            .collect();
+
+        ScenarioDef {
+            seed: self.seed,
+            environment: EnvironmentDef {
+                width: env.width_m,
+                height: env.height_m,
+            },
+            obstacles: env
+                .obstacles
+                .iter()
+                .map(|obs| ObstacleDef {
+                    x: obs.x,
+                    y: obs.y,
+                    radius: obs.radius,
+                })
+                .collect(),
+            walls: env
+                .walls
+                .iter()
+                .map(|wall| WallDef {
+                    x1: wall.x1,
+                    y1: wall.y1,
+                    x2: wall.x2,
+                    y2: wall.y2,
+                })
+                .collect(),
+            polygons: env
+                .polygons
+                .iter()
+                .map(|polygon| PolygonDef {
+                    points: points_def(&polygon.points),
+                })
+                .collect(),
+            objects: env
+                .objects
+                .iter()
+                .map(|object| ObjectDef {
+                    x: object.x,
+                    y: object.y,
+                    radius: object.radius,
+                    color: object.color.to_hex_string(),
+                })
+                .collect(),
+            goals: goals,
+            robots: robots,
+        }
+    }
+}
+
+fn goal_shape_from_def(def: &GoalDef, idx: usize) -> Result<GoalShape, Box<dyn Error>> {
+    match (def.x, def.y, def.radius, &def.points) {
+        (Some(x), Some(y), Some(radius), None) => {
+            if !is_positive(radius) {
+                return Err(format!(
+                    "goals[{}]: radius must be positive, got {}",
+                    idx, radius
+                ))?;
+            }
+            Ok(GoalShape::Circle {
+                x: x,
+                y: y,
+                radius: radius,
+            })
+        }
+        (None, None, None, Some(points)) => {
+            let points = polygon_points_from_def(points, "goals", idx)?;
+            Ok(GoalShape::Polygon(points))
+        }
+        _ => Err(format!(
+            "goals[{}]: give either x, y and radius or points, not a mix",
+            idx
+        ))?,
+    }
+}
+
+fn polygon_points_from_def(
+    points: &[[f32; 2]],
+    section: &str,
+    idx: usize,
+) -> Result<Vec<Vec3d<f32>>, Box<dyn Error>> {
+    if points.len() < 3 {
+        return Err(format!(
+            "{}[{}]: a polygon needs at least 3 points, got {}",
+            section,
+            idx,
+            points.len()
+        ))?;
+    }
+
+    Ok(points.iter().map(|p| Vec3d::new(p[0], p[1], 0.0)).collect())
+}
+
+fn sensor_from_def(def: &SensorDef, idx: usize) -> Result<SensorConfig, Box<dyn Error>> {
+    let mut config = SensorConfig::default();
+
+    if let Some(num_rays) = def.num_rays {
+        if num_rays > SensorConfig::MAX_RAYS {
+            return Err(format!(
+                "robots[{}].sensor: num_rays can be at most {}, got {}",
+                idx,
+                SensorConfig::MAX_RAYS,
+                num_rays
+            ))?;
+        }
+        config.num_rays = num_rays;
+    }
+
+    if let Some(fov) = def.fov {
+        if !is_positive(fov) || fov > 360.0 {
+            return Err(format!(
+                "robots[{}].sensor: fov must be in (0, 360] degrees, got {}",
+                idx, fov
+            ))?;
+        }
+        config.fov_rad = fov * TO_RAD_F32;
+    }
+
+    if let Some(range) = def.range {
+        if !is_positive(range) {
+            return Err(format!(
+                "robots[{}].sensor: range must be positive, got {}",
+                idx, range
+            ))?;
+        }
+        config.max_range = range;
+    }
+
+    if let Some(offset) = def.offset {
+        config.angle_offset_rad = offset * TO_RAD_F32;
+    }
+
+    if let Some(noise) = def.noise {
+        if noise < 0.0 {
+            return Err(format!(
+                "robots[{}].sensor: noise must not be negative, got {}",
+                idx, noise
+            ))?;
+        }
+        config.range_noise_std_dev = noise;
+    }
+
+    if let Some(dropout) = def.dropout {
+        if !(0.0..=1.0).contains(&dropout) {
+            return Err(format!(
+                "robots[{}].sensor: dropout must be in [0, 1], got {}",
+                idx, dropout
+            ))?;
+        }
+        config.dropout_prob = dropout;
+    }
+
+    Ok(config)
+}
+
+fn is_positive(value: f32) -> bool {
+    value > 0.0
+}
+
+fn parse_color(color_str: &str, section: &str, idx: usize) -> Result<Color, Box<dyn Error>> {
+    match Color::parse(color_str) {
+        Some(color) => Ok(color),
+        None => Err(format!(
+            "{}[{}]: unknown color '{}' (use a name or #rrggbb[aa])",
+            section, idx, color_str
+        ))?,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOD_SCENARIO: &str = r##"
+        seed = 1234
+
+        [environment]
+        width = 20.0
+        height = 10.0
+
+        [[obstacles]]
+        x = 5.0
+        y = 5.0
+        radius = 1.0
+
+        [[walls]]
+        x1 = 1.0
+        y1 = 1.0
+        x2 = 1.0
+        y2 = 9.0
+
+        [[polygons]]
+        points = [[10.0, 6.0], [14.0, 6.0], [14.0, 9.0], [12.0, 7.0], [10.0, 9.0]]
+
+        [[objects]]
+        x = 15.0
+        y = 5.0
+        radius = 0.5
+        color = "#00ff00"
+
+        [[goals]]
+        name = "home"
+        x = 2.0
+        y = 8.0
+        radius = 1.5
+        notify = true
+
+        [[goals]]
+        name = "dock"
+        points = [[17.0, 1.0], [19.0, 1.0], [19.0, 3.0], [17.0, 3.0]]
+        color = "#0000ff40"
+
+        [[robots]]
+        id = 1
+        x = 2.0
+        y = 2.0
+        heading = 90.0
+        color = "blue"
+        max_vel = 1.0
+        max_angular_vel = 20.0
+
+        [robots.sensor]
+        num_rays = 8
+        fov = 90.0
+        range = 5.0
+        noise = 0.1
+
+        [[robots]]
+        id = 2
+        x = 3.0
+        y = 3.0
+    "##;
+
+    #[test]
+    fn test_parse_scenario() {
+        let scenario = Scenario::from_toml_str(GOOD_SCENARIO).unwrap();
+
+        assert_eq!(scenario.seed, Some(1234));
+        assert_eq!(scenario.env.width_m, 20.0);
+        assert_eq!(scenario.env.height_m, 10.0);
+        assert_eq!(scenario.env.obstacles, vec![Obstacle::new(5.0, 5.0, 1.0)]);
+        assert_eq!(scenario.env.walls, vec![Wall::new(1.0, 1.0, 1.0, 9.0)]);
+        assert_eq!(scenario.env.polygons.len(), 1);
+        assert_eq!(scenario.env.polygons[0].points[3], Vec3d::new(12.0, 7.0, 0.0));
+        assert_eq!(
+            scenario.env.objects,
+            vec![Object::new(15.0, 5.0, 0.5, Color::new(0, 255, 0, 255))]
+        );
+
+        assert_eq!(scenario.env.goals.len(), 2);
+        assert_eq!(scenario.env.goals[0].name, "home");
+        assert!(scenario.env.goals[0].notify);
+        assert_eq!(
+            scenario.env.goals[0].shape,
+            GoalShape::Circle {
+                x: 2.0,
+                y: 8.0,
+                radius: 1.5
+            }
+        );
+        assert!(!scenario.env.goals[1].notify);
+        assert!(scenario.env.goals[1].contains(Vec3d::new(18.0, 2.0, 0.0)));
+
+        assert_eq!(scenario.robots.len(), 2);
+        assert_eq!(scenario.robots[0].color, Color::BLUE);
+        assert_eq!(scenario.robots[0].pos.heading_rad, 90.0 * TO_RAD_F32);
+        assert_eq!(scenario.robots[1].color, Color::BLACK);
+        assert_eq!(scenario.robots[1].radius, 0.5);
+        assert_eq!(scenario.robots[1].max_vel, 1.0);
+        assert_eq!(scenario.robots[1].max_angular_vel_deg, 20.0);
+
+        let sensor = scenario.robots[0].sensor;
+        assert_eq!(sensor.num_rays, 8);
+        assert_eq!(sensor.fov_rad, 90.0 * TO_RAD_F32);
+        assert_eq!(sensor.max_range, 5.0);
+        assert_eq!(sensor.range_noise_std_dev, 0.1);
+        assert_eq!(sensor.dropout_prob, 0.0);
+        assert_eq!(scenario.robots[1].sensor, SensorConfig::default());
+    }
+
+    #[test]
+    fn test_default_scenario_file_matches_builtin() {
+        let scenario = Scenario::from_toml_str(include_str!("../scenarios/default.toml")).unwrap();
+        assert_eq!(scenario, Scenario::default_scenario());
+    }
+
+    #[test]
+    fn test_save_round_trip() {
+        let mut scenario = Scenario::default_scenario();
+        scenario.seed = Some(7);
+        let points = vec![
+            Vec3d::new(1.0, 1.0, 0.0),
+            Vec3d::new(2.5, 1.0, 0.0),
+            Vec3d::new(1.0, 2.5, 0.0),
+        ];
+        scenario.env.add_polygon(Polygon::new(points.clone()));
+        let dock = GoalShape::Polygon(points);
+        scenario.env.add_goal(Goal::new("dock", dock, Color::BLUE, false));
+        scenario.robots[0].sensor.num_rays = 3;
+
+        let contents = scenario.to_toml_string().unwrap();
+        assert_eq!(Scenario::from_toml_str(&contents).unwrap(), scenario);
+    }
+
+    #[test]
+    fn test_errors_name_offending_entry() {
+        let bad_radius = GOOD_SCENARIO.replace("radius = 1.0", "radius = -1.0");
+        let err = Scenario::from_toml_str(&bad_radius).unwrap_err();
+        assert!(err.to_string().starts_with("obstacles[0]:"), "{}", err);
+
+        let dup_id = GOOD_SCENARIO.replace("id = 2", "id = 1");
+        let err = Scenario::from_toml_str(&dup_id).unwrap_err();
+        assert!(err.to_string().starts_with("robots[1]:"), "{}", err);
+
+        let bad_dropout = GOOD_SCENARIO.replace("noise = 0.1", "dropout = 1.5");
+        let err = Scenario::from_toml_str(&bad_dropout).unwrap_err();
+        assert!(err.to_string().starts_with("robots[0].sensor:"), "{}", err);
+
+        let dense_sensor = GOOD_SCENARIO.replace("num_rays = 8", "num_rays = 10000000");
+        let err = Scenario::from_toml_str(&dense_sensor).unwrap_err();
+        assert!(err.to_string().starts_with("robots[0].sensor:"), "{}", err);
+
+        let mixed_goal = GOOD_SCENARIO.replace("name = \"dock\"", "name = \"dock\"\nradius = 1.0");
+        let err = Scenario::from_toml_str(&mixed_goal).unwrap_err();
+        assert!(err.to_string().starts_with("goals[1]:"), "{}", err);
+
+        let small_polygon = GOOD_SCENARIO.replace("[14.0, 6.0], [14.0, 9.0], [12.0, 7.0], ", "");
+        let err = Scenario::from_toml_str(&small_polygon).unwrap_err();
+        assert!(err.to_string().starts_with("polygons[0]:"), "{}", err);
+
+        let bad_color = GOOD_SCENARIO.replace("#00ff00", "mauve");
+        let err = Scenario::from_toml_str(&bad_color).unwrap_err();
+        assert!(err.to_string().starts_with("objects[0]:"), "{}", err);
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::{Duration, Instant};
+
+/// Decides how many fixed simulation steps are due, so simulated time moves at
+/// a steady multiple of real time no matter how often the caller checks.
+pub struct SimClock {
+    time_step: f32,
+    // simulated seconds per real second; None runs as fast as possible
+    real_time_factor: Option<f32>,
+    paused: bool,
+    last_check: Instant,
+    // simulated time that's come due but hasn't been stepped through yet
+    owed_s: f64,
+}
+
+impl SimClock {
+    // if the console falls this far behind, drop the backlog rather than
+    // stalling to catch up
+    const MAX_STEPS_PER_CHECK: u32 = 10;
+
+    pub fn new(time_step: f32, real_time_factor: Option<f32>) -> Self {
+        Self {
+            time_step: time_step,
+            real_time_factor: real_time_factor,
+            paused: false,
+            last_check: Instant::now(),
+            owed_s: 0.0,
+        }
+    }
+
+    pub fn is_as_fast_as_possible(&self) -> bool {
+        self.real_time_factor.is_none()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn toggle_paused(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn steps_due(&mut self) -> u32 {
+        let now = Instant::now();
+        let real_elapsed = now - self.last_check;
+        self.last_check = now;
+        self.steps_due_after(real_elapsed)
+    }
+
+    fn steps_due_after(&mut self, real_elapsed: Duration) -> u32 {
+        if self.paused {
+            return 0;
+        }
+
+        let factor = match self.real_time_factor {
+            Some(factor) => factor as f64,
+            None => return 1,
+        };
+
+        self.owed_s += real_elapsed.as_secs_f64() * factor;
+        let time_step = self.time_step as f64;
+        let steps = (self.owed_s / time_step).floor() as u32;
+        if steps > SimClock::MAX_STEPS_PER_CHECK {
+            self.owed_s %= time_step;
+            return SimClock::MAX_STEPS_PER_CHECK;
+        }
+
+        self.owed_s -= steps as f64 * time_step;
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_due() {
+        let mut clock = SimClock::new(0.25, Some(1.0));
+        assert_eq!(clock.steps_due_after(Duration::from_millis(100)), 0);
+        assert_eq!(clock.steps_due_after(Duration::from_millis(200)), 1);
+        assert_eq!(clock.steps_due_after(Duration::from_millis(200)), 1);
+
+        // ten times real time
+        let mut clock = SimClock::new(0.25, Some(10.0));
+        assert_eq!(clock.steps_due_after(Duration::from_millis(100)), 4);
+
+        // a long stall doesn't turn into a burst of catch-up steps
+        assert_eq!(clock.steps_due_after(Duration::from_secs(60)), 10);
+        assert_eq!(clock.steps_due_after(Duration::from_millis(30)), 1);
+
+        let mut clock = SimClock::new(0.25, None);
+        assert_eq!(clock.steps_due_after(Duration::from_millis(0)), 1);
+
+        clock.toggle_paused();
+        assert_eq!(clock.steps_due_after(Duration::from_secs(1)), 0);
+    }
+}
@@ -1,15 +1,25 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use std::f64::consts::PI;
+use std::f32::consts::PI;
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 use robo_sim_utils;
 use robo_sim_utils::color::*;
+use robo_sim_utils::object_reading::*;
 use robo_sim_utils::ray::*;
 use robo_sim_utils::robot_position::*;
+use robo_sim_utils::sensor_config::*;
 use robo_sim_utils::vec3d;
+use robo_sim_utils::derive_seed;
 
 use super::console_comms::ConsoleComms;
+use super::lockstep::Lockstep;
+use super::sim_clock::SimClock;
 use super::environment::Environment;
+use super::environment::Object;
 use super::environment::Robot as EnvRobot;
 
 #[derive(Clone, Copy)]
@@ -18,6 +28,13 @@ struct RobotInfo {
     max_vel: f32,
     max_angular_vel: f32,
     radius: f32,
+    sensor: SensorConfig,
+    carrying: Option<Object>,
+    registered_at_s: f64,
+    // the latest move request, applied every step until the robot sends another
+    velocity_cmd: Option<vec3d::Vec3d<f32>>,
+    // turning requested since the last step
+    pending_spin: f32,
 }
 
 impl RobotInfo {
@@ -32,25 +49,183 @@ impl RobotInfo {
             max_vel: max_vel,
             max_angular_vel: max_angular_vel,
             radius: radius,
+            sensor: SensorConfig::default(),
+            carrying: None,
+            registered_at_s: 0.0,
+            velocity_cmd: None,
+            pending_spin: 0.0,
         }
     }
+
+    pub fn intersect_with_ray(&self, ray: &Ray<f32>) -> Option<vec3d::Vec3d<f32>> {
+        ray.intersect_with_circle_2d(self.pos.location.x, self.pos.location.y, self.radius)
+    }
+
+    pub fn sweep_circle(&self, ray: &Ray<f32>, radius: f32) -> Option<SweepHit<f32>> {
+        let (x, y) = (self.pos.location.x, self.pos.location.y);
+        ray.sweep_circle_against_circle_2d(radius, x, y, self.radius)
+    }
+}
+
+/// A robot entering or leaving one of the environment's goals. `goal_id` is
+/// the goal's index in `Environment::goals`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GoalEvent {
+    pub robot_id: u32,
+    pub goal_id: u32,
+    pub entered: bool,
+    pub elapsed: Duration,
 }
 
 pub struct Simulator {
     robots: HashMap<u32, RobotInfo>,
     time_step: f32,
+    sim_time: f64,
+    wall_sliding: bool,
+    seed: u64,
+    // one stream per robot, so the noise a robot sees doesn't depend on how
+    // the other robots' requests happen to interleave with its own
+    sensor_rngs: HashMap<u32, StdRng>,
+    goal_occupancy: HashSet<(u32, usize)>,
+    goal_events: Vec<GoalEvent>,
+    lockstep: Option<Lockstep>,
 }
 
 impl Simulator {
-    const NUM_OF_SIM_RAYS: u32 = 16;
+    // how far past touching an object a robot can still reach to pick it up
+    const PICKUP_REACH: f32 = 0.25;
+
+    // how far short of a contact point a robot is stopped, so it isn't left
+    // touching (and stuck to) whatever it hit
+    const COLLISION_SKIN: f32 = 0.0001;
+    const MAX_SLIDE_ITERATIONS: u32 = 3;
 
     pub fn new(time_step: f32) -> Self {
         Self {
             robots: HashMap::new(),
             time_step: time_step,
+            sim_time: 0.0,
+            wall_sliding: false,
+            seed: rand::random(),
+            sensor_rngs: HashMap::new(),
+            goal_occupancy: HashSet::new(),
+            goal_events: vec![],
+            lockstep: None,
         }
     }
 
+    /// Seeds the sensor noise. Has to happen before any robots register.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn set_wall_sliding(&mut self, wall_sliding: bool) {
+        self.wall_sliding = wall_sliding;
+    }
+
+    /// Switches to synchronous stepping. Has to happen before any robots
+    /// register, since they're told which mode to run in when they start.
+    pub fn set_lockstep(&mut self, lockstep: Option<Lockstep>) {
+        self.lockstep = lockstep;
+    }
+
+    pub fn time_step(&self) -> f32 {
+        self.time_step
+    }
+
+    /// Simulated seconds since the simulator started.
+    pub fn sim_time(&self) -> f64 {
+        self.sim_time
+    }
+
+    /// Advances simulated time by one time step, moving every robot by its
+    /// latest velocity command.
+    pub fn step(&mut self, env: &mut Environment) {
+        // a fixed order keeps robot-robot collisions from depending on
+        // HashMap iteration order
+        let mut ids: Vec<u32> = self.robots.keys().copied().collect();
+        ids.sort_unstable();
+
+        for id in ids {
+            let robot = self.robots.get_mut(&id).unwrap();
+            let spin = std::mem::take(&mut robot.pending_spin);
+            let velocity_cmd = robot.velocity_cmd;
+
+            if spin != 0.0 {
+                self.spin_robot(id, spin, env);
+            }
+            if let Some(v) = velocity_cmd {
+                self.move_robot(id, v.x, v.y, env);
+            }
+        }
+
+        self.sim_time += self.time_step as f64;
+    }
+
+    /// Steps the simulation as far as the clock says it's due. In lockstep
+    /// mode that means starting a tick, or stepping once every robot has
+    /// finished the one in progress.
+    pub fn advance(&mut self, clock: &mut SimClock, env: &mut Environment, comms: &ConsoleComms) {
+        let lockstep = match self.lockstep.as_mut() {
+            Some(lockstep) => lockstep,
+            None => {
+                for _ in 0..clock.steps_due() {
+                    self.step(env);
+                }
+                return;
+            }
+        };
+
+        // the robots are paused too, so they won't be finishing any ticks
+        if clock.is_paused() {
+            lockstep.hold();
+            return;
+        }
+
+        if lockstep.is_waiting() {
+            if lockstep.try_finish() {
+                self.step(env);
+            }
+            return;
+        }
+
+        if !lockstep.can_start_tick(self.robots.len()) {
+            return;
+        }
+
+        if clock.steps_due() > 0 {
+            let mut ids: Vec<u32> = self.robots.keys().copied().collect();
+            ids.sort_unstable();
+            for id in &ids {
+                comms.send_tick_msg(*id, lockstep.tick(), self.sim_time);
+            }
+            lockstep.start_tick(ids.into_iter());
+        }
+    }
+
+    pub fn tick_done(&mut self, id: u32, tick: u64) {
+        match self.lockstep.as_mut() {
+            Some(lockstep) => lockstep.robot_done(id, tick),
+            None => println!("Error: robot {} finished a tick outside of lockstep mode", id),
+        };
+    }
+
+    /// Replaces the robot's velocity command, given in its own frame.
+    pub fn set_velocity_cmd(&mut self, id: u32, x: f32, y: f32) {
+        match self.robots.get_mut(&id) {
+            Some(robot) => robot.velocity_cmd = Some(vec3d::Vec3d::new(x, y, 0.0)),
+            None => println!("Error: tried to move an unregistered robot: {}", id),
+        };
+    }
+
+    /// Queues a turn in place for the next step.
+    pub fn queue_spin(&mut self, id: u32, theta: f32) {
+        match self.robots.get_mut(&id) {
+            Some(robot) => robot.pending_spin += theta,
+            None => println!("Error: tried to spin an unregistered robot: {}", id),
+        };
+    }
+
     pub fn register_robot(
         &mut self,
         id: u32,
@@ -62,12 +237,30 @@ impl Simulator {
         env: &mut Environment,
         comms: &ConsoleComms,
     ) {
-        self.robots.insert(
-            id,
-            RobotInfo::new(pos, max_vel, max_angular_vel, radius),
-        );
+        let mut robot = RobotInfo::new(pos, max_vel, max_angular_vel, radius);
+        robot.registered_at_s = self.sim_time;
+        self.robots.insert(id, robot);
         env.add_robot(EnvRobot::new(id, pos, color));
-        comms.send_start_msg(id);
+        self.update_goals(id, env);
+        comms.send_start_msg(id, self.lockstep.is_some());
+    }
+
+    pub fn set_sensor_config(&mut self, id: u32, config: SensorConfig) {
+        // its readings would be too long to send back
+        if config.num_rays > SensorConfig::MAX_RAYS {
+            println!(
+                "Error: robot {} asked for {} sensor rays, but the most is {}",
+                id,
+                config.num_rays,
+                SensorConfig::MAX_RAYS
+            );
+            return;
+        }
+
+        match self.robots.get_mut(&id) {
+            Some(robot) => robot.sensor = config,
+            None => println!("Error: tried to configure an unregistered robot: {}", id),
+        };
     }
 
     pub fn update_robot_pos(&mut self, id: u32, pos: RobotPosition, env: &mut Environment) {
@@ -76,8 +269,59 @@ impl Simulator {
                 robot.pos = pos;
                 env.update_robot_pos(id, pos);
             }
-            None => println!("Error: tried to update an unregistered robot: {}", id),
+            None => {
+                println!("Error: tried to update an unregistered robot: {}", id);
+                return;
+            }
+        };
+
+        self.update_goals(id, env);
+    }
+
+    fn update_goals(&mut self, id: u32, env: &Environment) {
+        let robot = match self.robots.get(&id) {
+            Some(robot) => robot,
+            None => return,
         };
+
+        for (goal_idx, goal) in env.goals.iter().enumerate() {
+            let inside = goal.contains(robot.pos.location);
+            let was_inside = self.goal_occupancy.contains(&(id, goal_idx));
+            if inside == was_inside {
+                continue;
+            }
+
+            let elapsed = Duration::from_secs_f64(self.sim_time - robot.registered_at_s);
+            if inside {
+                self.goal_occupancy.insert((id, goal_idx));
+                println!(
+                    "robot {} reached goal '{}' after {:.2}s",
+                    id,
+                    goal.name,
+                    elapsed.as_secs_f32()
+                );
+            } else {
+                self.goal_occupancy.remove(&(id, goal_idx));
+                println!(
+                    "robot {} left goal '{}' after {:.2}s",
+                    id,
+                    goal.name,
+                    elapsed.as_secs_f32()
+                );
+            }
+
+            self.goal_events.push(GoalEvent {
+                robot_id: id,
+                goal_id: goal_idx as u32,
+                entered: inside,
+                elapsed: elapsed,
+            });
+        }
+    }
+
+    /// Hands back every goal entry/exit since the last call.
+    pub fn take_goal_events(&mut self) -> Vec<GoalEvent> {
+        std::mem::take(&mut self.goal_events)
     }
 
     pub fn get_robot_pos(&self, id: u32) -> Option<RobotPosition> {
@@ -108,33 +352,38 @@ impl Simulator {
         pos
     }
 
+    fn intersect_other_robots<'a>(
+        &'a self,
+        id: u32,
+        ray: &'a Ray<f32>,
+    ) -> impl Iterator<Item = vec3d::Vec3d<f32>> + 'a {
+        self.robots
+            .iter()
+            .filter(move |(other_id, _)| **other_id != id)
+            .filter_map(move |(_, other)| other.intersect_with_ray(ray))
+    }
+
     fn get_closest_reading(
         &self,
+        id: u32,
         robot_pos: &RobotPosition,
-        ray_num: u32,
+        ray_angle: f32,
+        max_range: f32,
         env: &Environment,
     ) -> Option<vec3d::Vec3d<f32>> {
-        // create the ray_num'th ray
-        const FULL_CIRCLE_RAD: f32 = (2.0 * PI) as f32;
+        // create the ray at ray_angle from the robot's heading
         let v = vec3d::Vec3d::<f32>::new(1.0, 0.0, 0.0);
-        let rad_per_ray = FULL_CIRCLE_RAD / (Simulator::NUM_OF_SIM_RAYS as f32);
-        let ray_angle = (ray_num as f32) * rad_per_ray;
         let v = v.rotated_z(robot_pos.heading_rad + ray_angle);
         let v = v + robot_pos.location;
         let ray = Ray::new(robot_pos.location, v);
 
         // look for the closest reading
-        let obs_readings = env
-            .obstacles
-            .iter()
-            .filter_map(|obs| obs.intersect_with_ray(&ray));
-        let wall_readings = env
-            .walls
-            .iter()
-            .filter_map(|wall| wall.intersect_with_ray(&ray));
+        let shape_reading = env.closest_shape_hit(&ray, max_range);
+        let robot_readings = self.intersect_other_robots(id, &ray);
 
-        obs_readings
-            .chain(wall_readings)
+        shape_reading
+            .into_iter()
+            .chain(robot_readings)
             .map(|reading| Simulator::global_to_egocentric(&robot_pos, reading))
             .reduce(|accum, reading| {
                 if accum.len_sq() <= reading.len_sq() {
@@ -143,17 +392,62 @@ impl Simulator {
                     reading
                 }
             })
+            .filter(|reading| reading.len() <= max_range)
     }
 
-    pub fn get_obs_readings(&self, id: u32, env: &mut Environment) -> Vec<vec3d::Vec3d<f32>> {
-        let robot = self.robots.get(&id);
-        if robot.is_none() {
-            return vec![];
+    fn sensor_rng(seed: u64, id: u32) -> StdRng {
+        // the robot process seeds its own behaviors with derive_seed(seed, id),
+        // so branch off of that rather than reuse it
+        StdRng::seed_from_u64(derive_seed(derive_seed(seed, id), 0))
+    }
+
+    // Box-Muller transform, so we don't need a whole distributions crate
+    fn gaussian_noise(rng: &mut StdRng, std_dev: f32) -> f32 {
+        let u1 = 1.0 - rng.gen::<f32>(); // (0, 1], safe to take the log of
+        let u2 = rng.gen::<f32>();
+        std_dev * (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+
+    fn add_sensor_error(
+        rng: &mut StdRng,
+        reading: vec3d::Vec3d<f32>,
+        sensor: &SensorConfig,
+    ) -> Option<vec3d::Vec3d<f32>> {
+        if sensor.dropout_prob > 0.0 && rng.gen::<f32>() < sensor.dropout_prob {
+            return None;
         }
 
-        let robot = robot.unwrap();
-        let readings = (0..Simulator::NUM_OF_SIM_RAYS)
-            .filter_map(|ray_num| self.get_closest_reading(&robot.pos, ray_num, env))
+        if sensor.range_noise_std_dev <= 0.0 {
+            return Some(reading);
+        }
+
+        let range = reading.len();
+        let noisy_range = (range + Simulator::gaussian_noise(rng, sensor.range_noise_std_dev)).max(0.0);
+        Some(reading * (noisy_range / range))
+    }
+
+    pub fn get_obs_readings(&mut self, id: u32, env: &mut Environment) -> Vec<vec3d::Vec3d<f32>> {
+        let robot = match self.robots.get(&id) {
+            Some(robot) => *robot,
+            None => return vec![],
+        };
+
+        let sensor = robot.sensor;
+        let readings = (0..sensor.num_rays)
+            .filter_map(|ray_num| {
+                let ray_angle = sensor.ray_angle(ray_num);
+                self.get_closest_reading(id, &robot.pos, ray_angle, sensor.max_range, env)
+            })
+            .collect::<Vec<vec3d::Vec3d<f32>>>();
+
+        let seed = self.seed;
+        let rng = self
+            .sensor_rngs
+            .entry(id)
+            .or_insert_with(|| Simulator::sensor_rng(seed, id));
+        let readings = readings
+            .into_iter()
+            .filter_map(|reading| Simulator::add_sensor_error(rng, reading, &sensor))
             .collect::<Vec<vec3d::Vec3d<f32>>>();
 
         let global_reading_positions = readings
@@ -165,9 +459,121 @@ impl Simulator {
         readings
     }
 
+    // true if an obstacle or wall sits between from_vec and to_vec
+    fn line_of_sight_blocked(
+        from_vec: vec3d::Vec3d<f32>,
+        to_vec: vec3d::Vec3d<f32>,
+        env: &Environment,
+    ) -> bool {
+        let ray = Ray::new(from_vec, to_vec);
+        let dist = (to_vec - from_vec).len();
+
+        env.closest_shape_hit(&ray, dist)
+            .is_some_and(|hit| (hit - from_vec).len() < dist)
+    }
+
+    pub fn get_object_readings(
+        &self,
+        id: u32,
+        max_range: f32,
+        fov_rad: f32,
+        env: &Environment,
+    ) -> Vec<ObjectReading> {
+        let robot = match self.robots.get(&id) {
+            Some(robot) => robot,
+            None => return vec![],
+        };
+
+        env.objects
+            .iter()
+            .filter_map(|object| {
+                let location = vec3d::Vec3d::new(object.x, object.y, 0.0);
+                let pos = Simulator::global_to_egocentric(&robot.pos, location);
+                if pos.len() > max_range || pos.angle_rad().abs() > fov_rad / 2.0 {
+                    return None;
+                }
+
+                if Simulator::line_of_sight_blocked(robot.pos.location, location, env) {
+                    return None;
+                }
+
+                Some(ObjectReading::new(pos, object.radius, object.color))
+            })
+            .collect()
+    }
+
+    // returns whether the robot is carrying something afterward
+    pub fn pickup_object(&mut self, id: u32, env: &mut Environment) -> bool {
+        let robot = match self.robots.get_mut(&id) {
+            Some(robot) => robot,
+            None => {
+                println!("Error: unregistered robot tried to pick up: {}", id);
+                return false;
+            }
+        };
+
+        if robot.carrying.is_some() {
+            return true;
+        }
+
+        let closest = env
+            .objects
+            .iter()
+            .enumerate()
+            .map(|(idx, object)| {
+                let location = vec3d::Vec3d::new(object.x, object.y, 0.0);
+                let gap = (location - robot.pos.location).len() - robot.radius - object.radius;
+                (idx, gap)
+            })
+            .filter(|(_, gap)| *gap <= Simulator::PICKUP_REACH)
+            .reduce(|accum, candidate| if accum.1 <= candidate.1 { accum } else { candidate });
+
+        match closest {
+            Some((idx, _)) => {
+                let object = env.objects.remove(idx);
+                robot.carrying = Some(object);
+                env.set_robot_carrying(id, Some(object));
+                true
+            }
+            None => false,
+        }
+    }
+
+    // returns whether the robot is carrying something afterward
+    pub fn drop_object(&mut self, id: u32, env: &mut Environment) -> bool {
+        let robot = match self.robots.get_mut(&id) {
+            Some(robot) => robot,
+            None => {
+                println!("Error: unregistered robot tried to drop: {}", id);
+                return false;
+            }
+        };
+
+        let mut object = match robot.carrying.take() {
+            Some(object) => object,
+            None => return false,
+        };
+
+        // set it down just in front of the robot
+        let offset = vec3d::Vec3d::new(robot.radius + object.radius, 0.0, 0.0);
+        let location = robot.pos.location + offset.rotated_z(robot.pos.heading_rad);
+        object.x = location.x;
+        object.y = location.y;
+
+        env.add_object(object);
+        env.set_robot_carrying(id, None);
+        false
+    }
+
     pub fn robot_dying(&mut self, id: u32, env: &mut Environment) -> bool {
+        self.drop_object(id, env);
         env.remove_robot(id);
         self.robots.remove(&id);
+        self.sensor_rngs.remove(&id);
+        self.goal_occupancy.retain(|(robot_id, _)| *robot_id != id);
+        if let Some(lockstep) = self.lockstep.as_mut() {
+            lockstep.robot_gone(id);
+        }
         self.robots.is_empty()
     }
 
@@ -192,35 +598,66 @@ impl Simulator {
         vec3d::Vec3d::<f32>::new(dist, 0.0, 0.0).rotated_z(angle)
     }
 
+    fn first_collision(
+        &self,
+        id: u32,
+        ray: &Ray<f32>,
+        radius: f32,
+        env: &Environment,
+    ) -> Option<SweepHit<f32>> {
+        let shape_collision = env.first_shape_collision(ray, radius);
+        let robot_collisions = self
+            .robots
+            .iter()
+            .filter(|(other_id, _)| **other_id != id)
+            .filter_map(|(_, other)| other.sweep_circle(ray, radius));
+
+        shape_collision
+            .into_iter()
+            .chain(robot_collisions)
+            .reduce(|accum, hit| if accum.t <= hit.t { accum } else { hit })
+    }
+
     fn constrain_by_environment(
         &self,
+        id: u32,
         from_vec: vec3d::Vec3d<f32>,
         to_vec: vec3d::Vec3d<f32>,
         radius: f32,
         env: &Environment,
     ) -> vec3d::Vec3d<f32> {
-        let ray = Ray::new(from_vec, to_vec);
-        let mut delta = to_vec - from_vec;
-        let ray_len = delta.len();
+        let mut from_vec = from_vec;
+        let mut remaining = to_vec - from_vec;
+        let mut delta = vec3d::Vec3d::<f32>::new(0.0, 0.0, 0.0);
 
-        // look in the obstacles for the closest reading
-        let obs_collisions = env
-            .obstacles
-            .iter()
-            .filter_map(|obs| obs.intersect_with_ray(&ray));
-        let wall_collisions = env
-            .walls
-            .iter()
-            .filter_map(|wall| wall.intersect_with_ray(&ray));
+        for _ in 0..Simulator::MAX_SLIDE_ITERATIONS {
+            let ray = Ray::new(from_vec, from_vec + remaining);
+            let hit = match self.first_collision(id, &ray, radius, env) {
+                Some(hit) => hit,
+                None => {
+                    delta += remaining;
+                    break;
+                }
+            };
+
+            // stop just short of the contact point
+            let remaining_len = remaining.len();
+            let t = (hit.t - Simulator::COLLISION_SKIN / remaining_len).max(0.0);
+            let step = remaining * t;
+            delta += step;
+            from_vec += step;
 
-        let collision_dist = obs_collisions
-            .chain(wall_collisions)
-            .map(|collision| (collision - from_vec).len())
-            .filter(|collision_dist| *collision_dist < (ray_len + radius))
-            .reduce(f32::min);
+            if !self.wall_sliding {
+                break;
+            }
 
-        if collision_dist.is_some() {
-            delta = delta.to_unit() * (collision_dist.unwrap() - radius);
+            // keep whatever part of the leftover motion runs along the surface
+            let leftover = remaining * (1.0 - t);
+            let into_surface = leftover.dot(hit.normal);
+            remaining = leftover - hit.normal * into_surface.min(0.0);
+            if remaining.len() < Simulator::COLLISION_SKIN {
+                break;
+            }
         }
 
         delta
@@ -241,10 +678,15 @@ impl Simulator {
         let v = self.constrain_by_robot(requested, robot.max_vel, robot.max_angular_vel);
 
         let v = v.rotated_z(robot.pos.heading_rad); // switch to real-world direction
-        robot.pos.heading_rad = v.angle_rad(); // we've already got the new heading
+        if v.len_sq() > 0.0 {
+            // we've already got the new heading. a robot told to stand still
+            // keeps the one it has.
+            robot.pos.heading_rad = v.angle_rad();
+        }
 
         // make sure the robot doesn't violate any environmental constraints
         let v = self.constrain_by_environment(
+            id,
             robot.pos.location,
             robot.pos.location + v,
             robot.radius,
@@ -257,9 +699,500 @@ impl Simulator {
             self.robots.get_mut(&id).unwrap().pos = robot.pos;
         }
         env.update_robot_pos(id, robot.pos);
+        self.update_goals(id, env);
+    }
+
+    /// Puts a robot straight at location, as when it's dragged in the editor,
+    /// unless it would overlap a shape or another robot there. Returns whether
+    /// it moved.
+    pub fn teleport_robot(
+        &mut self,
+        id: u32,
+        location: vec3d::Vec3d<f32>,
+        env: &mut Environment,
+    ) -> bool {
+        let mut robot = match self.robots.get(&id) {
+            Some(robot) => *robot,
+            None => {
+                println!("Error: tried to teleport an unregistered robot: {}", id);
+                return false;
+            }
+        };
+
+        let overlaps_robot = self.robots.iter().any(|(other_id, other)| {
+            *other_id != id && (other.pos.location - location).len() < other.radius + robot.radius
+        });
+        if overlaps_robot || env.overlaps_shape(location, robot.radius) {
+            return false;
+        }
+
+        robot.pos.location = location;
+        self.update_robot_pos(id, robot.pos, env);
+        true
+    }
+
+    pub fn spin_robot(&mut self, id: u32, theta: f32, env: &mut Environment) {
+        let time_step = self.time_step;
+        let robot = match self.robots.get_mut(&id) {
+            Some(robot) => robot,
+            None => {
+                println!("Error: tried to spin an unregistered robot: {}", id);
+                return;
+            }
+        };
+
+        let max_turn = robot.max_angular_vel * time_step;
+        let theta = theta.max(-max_turn).min(max_turn);
+        robot.pos.heading_rad = robo_sim_utils::normalize_angle_pi(robot.pos.heading_rad + theta);
+
+        env.update_robot_pos(id, robot.pos);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use robo_sim_utils::TO_RAD_F32;
+
+    use super::super::environment::Robot as EnvRobot;
+    use super::super::environment::{Goal, GoalShape};
+    use super::super::environment::Object;
+    use super::super::environment::Obstacle;
+    use super::super::environment::Polygon;
+    use super::super::environment::Wall;
+
+    const EPS: f32 = 0.00001;
+
+    fn add_robot(
+        simulator: &mut Simulator,
+        env: &mut Environment,
+        id: u32,
+        pos: RobotPosition,
+        max_angular_vel: f32,
+    ) {
+        simulator
+            .robots
+            .insert(id, RobotInfo::new(pos, 10.0, max_angular_vel, 0.5));
+        env.add_robot(EnvRobot::new(id, pos, Color::BLUE));
+    }
+
+    #[test]
+    fn test_spin_robot() {
+        let mut simulator = Simulator::new(0.5);
+        let mut env = Environment::new(10.0, 10.0);
+        let pos = RobotPosition::new(vec3d::Vec3d::new(5.0, 5.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos, 90.0 * TO_RAD_F32);
+
+        // within the per-step limit
+        simulator.spin_robot(1, 30.0 * TO_RAD_F32, &mut env);
+        let heading = simulator.get_robot_pos(1).unwrap().heading_rad;
+        assert!((heading - 30.0 * TO_RAD_F32).abs() < EPS);
+
+        // clamped to max_angular_vel * time_step
+        simulator.spin_robot(1, -170.0 * TO_RAD_F32, &mut env);
+        let heading = simulator.get_robot_pos(1).unwrap().heading_rad;
+        assert!((heading - -15.0 * TO_RAD_F32).abs() < EPS);
+
+        // spinning doesn't translate, and the display copy is kept in sync
+        let pos = simulator.get_robot_pos(1).unwrap();
+        assert_eq!(pos.location, vec3d::Vec3d::new(5.0, 5.0, 0.0));
+        assert_eq!(env.robots[&1].pos, pos);
+    }
+
+    #[test]
+    fn test_step_integrates_latest_command() {
+        let mut simulator = Simulator::new(0.5);
+        let mut env = Environment::new(20.0, 20.0);
+        let pos = RobotPosition::new(vec3d::Vec3d::new(5.0, 5.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos, 90.0 * TO_RAD_F32);
+
+        // sending commands doesn't move anything by itself, however many arrive
+        for _ in 0..5 {
+            simulator.set_velocity_cmd(1, 2.0, 0.0);
+        }
+        assert_eq!(simulator.get_robot_pos(1).unwrap(), pos);
+
+        // the latest command keeps applying every step
+        simulator.step(&mut env);
+        simulator.step(&mut env);
+        let location = simulator.get_robot_pos(1).unwrap().location;
+        assert!((location.x - 7.0).abs() < EPS);
+        assert!((simulator.sim_time() - 1.0).abs() < EPS as f64);
+
+        simulator.set_velocity_cmd(1, 0.0, 0.0);
+        simulator.queue_spin(1, 10.0 * TO_RAD_F32);
+        simulator.queue_spin(1, 10.0 * TO_RAD_F32);
+        simulator.step(&mut env);
+        let pos = simulator.get_robot_pos(1).unwrap();
+        assert!((pos.location.x - 7.0).abs() < EPS);
+        assert!((pos.heading_rad - 20.0 * TO_RAD_F32).abs() < EPS);
+
+        // a queued spin only happens once
+        simulator.step(&mut env);
+        let heading = simulator.get_robot_pos(1).unwrap().heading_rad;
+        assert!((heading - 20.0 * TO_RAD_F32).abs() < EPS);
+    }
+
+    #[test]
+    fn test_lockstep_waits_while_paused() {
+        let mut simulator = Simulator::new(0.5);
+        let mut env = Environment::new(10.0, 10.0);
+        let pos = RobotPosition::new(vec3d::Vec3d::new(5.0, 5.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos, 90.0 * TO_RAD_F32);
+        simulator.set_lockstep(Some(Lockstep::new(Duration::from_millis(0), 1)));
+        let mut clock = SimClock::new(0.5, None);
+        let comms = ConsoleComms::new();
+
+        simulator.advance(&mut clock, &mut env, &comms);
+        clock.toggle_paused();
+        for _ in 0..3 {
+            simulator.advance(&mut clock, &mut env, &comms);
+        }
+        assert_eq!(simulator.sim_time(), 0.0);
+
+        // the tick that was going when it paused times out once it resumes
+        clock.toggle_paused();
+        simulator.advance(&mut clock, &mut env, &comms);
+        assert_eq!(simulator.sim_time(), 0.5);
+    }
+
+    #[test]
+    fn test_spin_robot_wraps_heading() {
+        let mut simulator = Simulator::new(1.0);
+        let mut env = Environment::new(10.0, 10.0);
+        let pos = RobotPosition::new(vec3d::Vec3d::new(5.0, 5.0, 0.0), 170.0 * TO_RAD_F32);
+        add_robot(&mut simulator, &mut env, 1, pos, 90.0 * TO_RAD_F32);
+
+        simulator.spin_robot(1, 20.0 * TO_RAD_F32, &mut env);
+        let heading = simulator.get_robot_pos(1).unwrap().heading_rad;
+        assert!((heading - -170.0 * TO_RAD_F32).abs() < EPS);
+    }
+
+    #[test]
+    fn test_robots_collide_with_each_other() {
+        let mut simulator = Simulator::new(1.0);
+        let mut env = Environment::new(10.0, 10.0);
+        let pos1 = RobotPosition::new(vec3d::Vec3d::new(1.0, 5.0, 0.0), 0.0);
+        let pos2 = RobotPosition::new(vec3d::Vec3d::new(4.0, 5.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos1, 90.0 * TO_RAD_F32);
+        add_robot(&mut simulator, &mut env, 2, pos2, 90.0 * TO_RAD_F32);
+
+        // robot 1 tries to drive through robot 2 and stops touching it
+        simulator.move_robot(1, 5.0, 0.0, &mut env);
+        let pos = simulator.get_robot_pos(1).unwrap();
+        let dist = (pos.location - vec3d::Vec3d::new(3.0, 5.0, 0.0)).len();
+        assert!(dist <= Simulator::COLLISION_SKIN + EPS);
     }
 
-    pub fn spin_robot(&mut self, id: u32, _theta: f32) {
-        println!("Error: spin_robot not implemented: {}", id);
+    #[test]
+    fn test_teleport_robot() {
+        let mut simulator = Simulator::new(1.0);
+        let mut env = Environment::new(10.0, 10.0);
+        env.add_wall(Wall::new(5.0, 0.0, 5.0, 10.0));
+        env.add_obstacle(Obstacle::new(8.0, 8.0, 1.0));
+        let pos = RobotPosition::new(vec3d::Vec3d::new(1.0, 5.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos, 90.0 * TO_RAD_F32);
+        let pos2 = RobotPosition::new(vec3d::Vec3d::new(8.0, 2.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 2, pos2, 90.0 * TO_RAD_F32);
+
+        // straight past the wall
+        let location = vec3d::Vec3d::new(7.0, 5.0, 0.0);
+        assert!(simulator.teleport_robot(1, location, &mut env));
+        assert_eq!(simulator.get_robot_pos(1).unwrap().location, location);
+        assert_eq!(env.robots[&1].pos.location, location);
+
+        // but not onto the wall, the obstacle or the other robot
+        for bad in &[(5.2, 5.0), (8.0, 7.5), (8.5, 2.0)] {
+            let bad = vec3d::Vec3d::new(bad.0, bad.1, 0.0);
+            assert!(!simulator.teleport_robot(1, bad, &mut env));
+        }
+        assert_eq!(simulator.get_robot_pos(1).unwrap().location, location);
+    }
+
+    #[test]
+    fn test_robot_body_hits_wall_corner() {
+        let mut simulator = Simulator::new(1.0);
+        let mut env = Environment::new(10.0, 10.0);
+        env.add_wall(Wall::new(5.0, 5.3, 5.0, 9.0));
+        let pos = RobotPosition::new(vec3d::Vec3d::new(1.0, 5.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos, 90.0 * TO_RAD_F32);
+
+        // the robot's center passes below the wall, but its body doesn't
+        simulator.move_robot(1, 8.0, 0.0, &mut env);
+        let pos = simulator.get_robot_pos(1).unwrap();
+        assert!((pos.location.x - 4.6).abs() <= Simulator::COLLISION_SKIN + EPS);
+        assert_eq!(pos.location.y, 5.0);
+    }
+
+    #[test]
+    fn test_polygon_blocks_and_is_sensed() {
+        let mut simulator = Simulator::new(1.0);
+        let mut env = Environment::new(20.0, 20.0);
+        env.add_polygon(Polygon::new(vec![
+            vec3d::Vec3d::new(4.0, 0.0, 0.0),
+            vec3d::Vec3d::new(8.0, 0.0, 0.0),
+            vec3d::Vec3d::new(8.0, 8.0, 0.0),
+            vec3d::Vec3d::new(4.0, 8.0, 0.0),
+        ]));
+        let pos = RobotPosition::new(vec3d::Vec3d::new(2.0, 4.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos, 90.0 * TO_RAD_F32);
+
+        let readings = simulator.get_obs_readings(1, &mut env);
+        assert!((readings[0] - vec3d::Vec3d::new(2.0, 0.0, 0.0)).len() < EPS);
+
+        // the robot's body stops at the polygon's face
+        for _ in 0..5 {
+            simulator.move_robot(1, 10.0, 0.0, &mut env);
+        }
+        let x = simulator.get_robot_pos(1).unwrap().location.x;
+        assert!((x - 3.5).abs() < Simulator::COLLISION_SKIN * 2.0);
+    }
+
+    #[test]
+    fn test_wall_sliding() {
+        let mut simulator = Simulator::new(1.0);
+        let mut env = Environment::new(10.0, 10.0);
+        env.add_wall(Wall::new(5.0, 0.0, 5.0, 10.0));
+        let pos = RobotPosition::new(vec3d::Vec3d::new(3.5, 3.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos, 90.0 * TO_RAD_F32);
+
+        // without sliding the robot stops dead at the wall
+        simulator.move_robot(1, 2.0, 2.0, &mut env);
+        let pos = simulator.get_robot_pos(1).unwrap();
+        assert!((pos.location - vec3d::Vec3d::new(4.5, 4.0, 0.0)).len() < 0.001);
+
+        // with sliding, the motion into the wall is dropped but the rest kept
+        simulator.set_wall_sliding(true);
+        simulator.robots.get_mut(&1).unwrap().pos = RobotPosition::new(
+            vec3d::Vec3d::new(3.5, 3.0, 0.0),
+            0.0,
+        );
+        simulator.move_robot(1, 2.0, 2.0, &mut env);
+        let pos = simulator.get_robot_pos(1).unwrap();
+        assert!((pos.location - vec3d::Vec3d::new(4.5, 5.0, 0.0)).len() < 0.001);
+    }
+
+    #[test]
+    fn test_robots_sense_each_other() {
+        let mut simulator = Simulator::new(1.0);
+        let mut env = Environment::new(10.0, 10.0);
+        let pos1 = RobotPosition::new(vec3d::Vec3d::new(1.0, 5.0, 0.0), 0.0);
+        let pos2 = RobotPosition::new(vec3d::Vec3d::new(4.0, 5.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos1, 90.0 * TO_RAD_F32);
+        add_robot(&mut simulator, &mut env, 2, pos2, 90.0 * TO_RAD_F32);
+
+        let readings = simulator.get_obs_readings(1, &mut env);
+        assert_eq!(readings.len(), 1);
+        assert!((readings[0] - vec3d::Vec3d::new(2.5, 0.0, 0.0)).len() < EPS);
+    }
+
+    #[test]
+    fn test_sensor_range_and_fov() {
+        let mut simulator = Simulator::new(1.0);
+        let mut env = Environment::new(20.0, 20.0);
+        env.add_obstacle(Obstacle::new(5.0, 10.0, 0.5)); // 4m ahead
+        env.add_obstacle(Obstacle::new(10.0, 17.0, 1.0)); // 6m to the left
+        env.add_obstacle(Obstacle::new(10.0, 3.0, 1.0)); // 6m to the right
+        let pos = RobotPosition::new(vec3d::Vec3d::new(1.0, 10.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos, 90.0 * TO_RAD_F32);
+
+        // a narrow forward cone only sees the obstacle straight ahead
+        let config = SensorConfig::new(3, 20.0 * TO_RAD_F32, 100.0, 0.0, 0.0, 0.0);
+        simulator.set_sensor_config(1, config);
+        let readings = simulator.get_obs_readings(1, &mut env);
+        assert_eq!(readings.len(), 1);
+        assert!((readings[0] - vec3d::Vec3d::new(3.5, 0.0, 0.0)).len() < EPS);
+
+        // offset to look left, but with too little range to reach anything
+        let config = SensorConfig::new(1, 0.0, 5.0, 90.0 * TO_RAD_F32, 0.0, 0.0);
+        simulator.set_sensor_config(1, config);
+        simulator.update_robot_pos(
+            1,
+            RobotPosition::new(vec3d::Vec3d::new(10.0, 10.0, 0.0), 0.0),
+            &mut env,
+        );
+        assert!(simulator.get_obs_readings(1, &mut env).is_empty());
+
+        // ... and enough range to see it
+        let config = SensorConfig::new(1, 0.0, 6.5, 90.0 * TO_RAD_F32, 0.0, 0.0);
+        simulator.set_sensor_config(1, config);
+        let readings = simulator.get_obs_readings(1, &mut env);
+        assert_eq!(readings.len(), 1);
+        assert!((readings[0] - vec3d::Vec3d::new(0.0, 6.0, 0.0)).len() < EPS);
+
+        // too many rays to send back is turned away, keeping the old config
+        let mut too_dense = config;
+        too_dense.num_rays = SensorConfig::MAX_RAYS + 1;
+        simulator.set_sensor_config(1, too_dense);
+        assert_eq!(simulator.get_obs_readings(1, &mut env).len(), 1);
+    }
+
+    #[test]
+    fn test_object_readings() {
+        let mut simulator = Simulator::new(1.0);
+        let mut env = Environment::new(20.0, 20.0);
+        env.add_object(Object::new(5.0, 10.0, 0.5, Color::RED)); // 4m ahead
+        env.add_object(Object::new(1.0, 13.0, 0.5, Color::BLUE)); // 3m to the left
+        env.add_object(Object::new(12.0, 10.0, 0.5, Color::BLACK)); // 11m ahead
+        env.add_object(Object::new(1.0, 5.0, 0.5, Color::BLUE)); // behind a wall
+        env.add_wall(Wall::new(0.0, 7.0, 2.0, 7.0));
+        let pos = RobotPosition::new(vec3d::Vec3d::new(1.0, 10.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos, 90.0 * TO_RAD_F32);
+
+        let readings = simulator.get_object_readings(1, 10.0, 2.0 * PI, &env);
+        assert_eq!(
+            readings,
+            vec![
+                ObjectReading::new(vec3d::Vec3d::new(4.0, 0.0, 0.0), 0.5, Color::RED),
+                ObjectReading::new(vec3d::Vec3d::new(0.0, 3.0, 0.0), 0.5, Color::BLUE),
+            ]
+        );
+
+        // narrowing the field of view drops the one off to the side
+        let readings = simulator.get_object_readings(1, 10.0, 90.0 * TO_RAD_F32, &env);
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].color, Color::RED);
+    }
+
+    #[test]
+    fn test_pickup_and_drop() {
+        let mut simulator = Simulator::new(1.0);
+        let mut env = Environment::new(20.0, 20.0);
+        env.add_object(Object::new(3.0, 10.0, 0.5, Color::RED));
+        env.add_object(Object::new(1.0, 11.2, 0.5, Color::BLUE));
+        let pos = RobotPosition::new(vec3d::Vec3d::new(1.0, 10.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos, 90.0 * TO_RAD_F32);
+
+        // nothing to drop yet
+        assert!(!simulator.drop_object(1, &mut env));
+
+        // the red object is out of reach, the blue one isn't
+        assert!(simulator.pickup_object(1, &mut env));
+        assert_eq!(env.objects, vec![Object::new(3.0, 10.0, 0.5, Color::RED)]);
+        assert_eq!(
+            env.robots[&1].carrying,
+            Some(Object::new(1.0, 11.2, 0.5, Color::BLUE))
+        );
+
+        // already carrying, so the robot keeps what it has
+        let pos = RobotPosition::new(vec3d::Vec3d::new(2.0, 10.0, 0.0), 0.0);
+        simulator.update_robot_pos(1, pos, &mut env);
+        assert!(simulator.pickup_object(1, &mut env));
+        assert_eq!(env.objects.len(), 1);
+
+        // drop it somewhere else
+        let pos = RobotPosition::new(vec3d::Vec3d::new(10.0, 10.0, 0.0), 90.0 * TO_RAD_F32);
+        simulator.update_robot_pos(1, pos, &mut env);
+        assert!(!simulator.drop_object(1, &mut env));
+        assert_eq!(env.robots[&1].carrying, None);
+        let dropped = env.objects[1];
+        assert_eq!(dropped.color, Color::BLUE);
+        assert!((dropped.x - 10.0).abs() < EPS);
+        assert!((dropped.y - 11.0).abs() < EPS);
+    }
+
+    #[test]
+    fn test_goal_entry_and_exit() {
+        let mut simulator = Simulator::new(1.0);
+        let mut env = Environment::new(20.0, 20.0);
+        let shape = GoalShape::Circle {
+            x: 5.0,
+            y: 2.0,
+            radius: 1.5,
+        };
+        env.add_goal(Goal::new("target", shape, Color::RED, true));
+        let pos = RobotPosition::new(vec3d::Vec3d::new(2.0, 2.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos, 90.0 * TO_RAD_F32);
+
+        simulator.move_robot(1, 1.0, 0.0, &mut env);
+        assert!(simulator.take_goal_events().is_empty());
+
+        // the robot's center crosses into the goal
+        simulator.move_robot(1, 1.0, 0.0, &mut env);
+        let events = simulator.take_goal_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].robot_id, events[0].goal_id), (1, 0));
+        assert!(events[0].entered);
+
+        // staying inside doesn't report it again
+        simulator.move_robot(1, 1.0, 0.0, &mut env);
+        assert!(simulator.take_goal_events().is_empty());
+
+        simulator.move_robot(1, 1.0, 0.0, &mut env);
+        simulator.move_robot(1, 1.0, 0.0, &mut env);
+        let events = simulator.take_goal_events();
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].entered);
+    }
+
+    #[test]
+    fn test_dying_robot_drops_object() {
+        let mut simulator = Simulator::new(1.0);
+        let mut env = Environment::new(20.0, 20.0);
+        env.add_object(Object::new(2.0, 10.0, 0.5, Color::RED));
+        let pos = RobotPosition::new(vec3d::Vec3d::new(1.0, 10.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos, 90.0 * TO_RAD_F32);
+
+        assert!(simulator.pickup_object(1, &mut env));
+        assert!(env.objects.is_empty());
+        simulator.robot_dying(1, &mut env);
+        assert_eq!(env.objects.len(), 1);
+    }
+
+    #[test]
+    fn test_sensor_noise_and_dropout() {
+        let mut simulator = Simulator::new(1.0);
+        let mut env = Environment::new(20.0, 20.0);
+        env.add_obstacle(Obstacle::new(5.0, 10.0, 1.0));
+        let pos = RobotPosition::new(vec3d::Vec3d::new(1.0, 10.0, 0.0), 0.0);
+        add_robot(&mut simulator, &mut env, 1, pos, 90.0 * TO_RAD_F32);
+
+        // everything dropped
+        let config = SensorConfig::new(1, 0.0, 100.0, 0.0, 0.0, 1.0);
+        simulator.set_sensor_config(1, config);
+        assert!(simulator.get_obs_readings(1, &mut env).is_empty());
+
+        // noise moves the reading along the ray, never off of it
+        let config = SensorConfig::new(1, 0.0, 100.0, 0.0, 0.5, 0.0);
+        simulator.set_sensor_config(1, config);
+        let mut total_range = 0.0;
+        const NUM_SAMPLES: u32 = 1000;
+        for _ in 0..NUM_SAMPLES {
+            let readings = simulator.get_obs_readings(1, &mut env);
+            assert_eq!(readings.len(), 1);
+            assert_eq!(readings[0].y, 0.0);
+            total_range += readings[0].x;
+        }
+        let mean_range = total_range / NUM_SAMPLES as f32;
+        assert!((mean_range - 3.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_sensor_noise_is_per_robot_and_seeded() {
+        let noisy_readings = |seed: u64, other_robot_reads_first: bool| {
+            let mut simulator = Simulator::new(1.0);
+            simulator.set_seed(seed);
+            let mut env = Environment::new(20.0, 20.0);
+            env.add_obstacle(Obstacle::new(5.0, 10.0, 1.0));
+            // robot 2 looks at the obstacle from the other side
+            let pos_1 = RobotPosition::new(vec3d::Vec3d::new(1.0, 10.0, 0.0), 0.0);
+            let pos_2 = RobotPosition::new(vec3d::Vec3d::new(9.0, 10.0, 0.0), PI);
+            for (id, pos) in [(1, pos_1), (2, pos_2)] {
+                add_robot(&mut simulator, &mut env, id, pos, 90.0 * TO_RAD_F32);
+                let config = SensorConfig::new(1, 0.0, 100.0, 0.0, 0.5, 0.0);
+                simulator.set_sensor_config(id, config);
+            }
+
+            if other_robot_reads_first {
+                simulator.get_obs_readings(2, &mut env);
+            }
+            (0..10)
+                .map(|_| simulator.get_obs_readings(1, &mut env)[0].x)
+                .collect::<Vec<f32>>()
+        };
+
+        assert_eq!(noisy_readings(7, false), noisy_readings(7, true));
+        assert_ne!(noisy_readings(7, false), noisy_readings(8, false));
     }
 }
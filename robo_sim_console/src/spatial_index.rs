@@ -0,0 +1,382 @@
+use robo_sim_utils::vec3d::*;
+
+/// Which of the environment's shape lists an index entry points into.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ShapeRef {
+    Obstacle(usize),
+    Wall(usize),
+    Polygon(usize),
+}
+
+/// A uniform grid over the environment's static shapes. Each shape is listed
+/// in every cell its bounding box touches, so a query only has to look at the
+/// shapes near where it's looking instead of all of them.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpatialIndex {
+    cell_size: f32,
+    cols: usize,
+    rows: usize,
+    cells: Vec<Vec<ShapeRef>>,
+    // shapes reaching past the edge of the grid, which every query checks
+    overflow: Vec<ShapeRef>,
+}
+
+impl SpatialIndex {
+    pub const DEFAULT_CELL_SIZE: f32 = 2.0;
+
+    pub fn new(width_m: f32, height_m: f32, cell_size: f32) -> Self {
+        let cols = ((width_m / cell_size).ceil() as usize).max(1);
+        let rows = ((height_m / cell_size).ceil() as usize).max(1);
+        Self {
+            cell_size: cell_size,
+            cols: cols,
+            rows: rows,
+            cells: vec![vec![]; cols * rows],
+            overflow: vec![],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+        }
+        self.overflow.clear();
+    }
+
+    pub fn overflow(&self) -> &[ShapeRef] {
+        &self.overflow
+    }
+
+    fn grid_max(&self) -> (f32, f32) {
+        (
+            self.cols as f32 * self.cell_size,
+            self.rows as f32 * self.cell_size,
+        )
+    }
+
+    fn cell_range(&self, min: Vec3d<f32>, max: Vec3d<f32>) -> Option<(usize, usize, usize, usize)> {
+        let (grid_max_x, grid_max_y) = self.grid_max();
+        if max.x < 0.0 || max.y < 0.0 || min.x > grid_max_x || min.y > grid_max_y {
+            return None;
+        }
+
+        let to_col = |x: f32| ((x / self.cell_size).floor().max(0.0) as usize).min(self.cols - 1);
+        let to_row = |y: f32| ((y / self.cell_size).floor().max(0.0) as usize).min(self.rows - 1);
+
+        Some((to_col(min.x), to_row(min.y), to_col(max.x), to_row(max.y)))
+    }
+
+    /// Lists `shape` in every cell touched by the box from `min` to `max`.
+    pub fn insert(&mut self, shape: ShapeRef, min: Vec3d<f32>, max: Vec3d<f32>) {
+        let (grid_max_x, grid_max_y) = self.grid_max();
+        if min.x < 0.0 || min.y < 0.0 || max.x > grid_max_x || max.y > grid_max_y {
+            self.overflow.push(shape);
+        }
+
+        if let Some((col0, row0, col1, row1)) = self.cell_range(min, max) {
+            for row in row0..=row1 {
+                for col in col0..=col1 {
+                    self.cells[row * self.cols + col].push(shape);
+                }
+            }
+        }
+    }
+
+    /// Every shape whose bounding box might touch the box from `min` to
+    /// `max`, each listed once.
+    pub fn query_box(&self, min: Vec3d<f32>, max: Vec3d<f32>) -> Vec<ShapeRef> {
+        let mut shapes = self.overflow.clone();
+        if let Some((col0, row0, col1, row1)) = self.cell_range(min, max) {
+            for row in row0..=row1 {
+                for col in col0..=col1 {
+                    shapes.extend_from_slice(&self.cells[row * self.cols + col]);
+                }
+            }
+        }
+
+        shapes.sort_by_key(|shape| match shape {
+            ShapeRef::Obstacle(idx) => (0, *idx),
+            ShapeRef::Wall(idx) => (1, *idx),
+            ShapeRef::Polygon(idx) => (2, *idx),
+        });
+        shapes.dedup();
+        shapes
+    }
+
+    /// Walks the cells along a ray, nearest first, out to `max_dist` from
+    /// `from_loc`. `visit` gets each cell's shapes along with how far along the
+    /// ray the cell ends, and returns true to stop the walk early. Shapes that
+    /// span several cells are passed more than once. Overflow shapes aren't
+    /// passed at all, so callers need to check those themselves.
+    pub fn walk_ray<F>(&self, from_loc: Vec3d<f32>, dir: Vec3d<f32>, max_dist: f32, mut visit: F)
+    where
+        F: FnMut(&[ShapeRef], f32) -> bool,
+    {
+        let dir_len = dir.len();
+        if dir_len == 0.0 {
+            return;
+        }
+        let dir = dir / dir_len;
+
+        // clip the ray to the grid so a walk can start from outside it
+        let (grid_max_x, grid_max_y) = self.grid_max();
+        let slab = |from: f32, d: f32, max: f32| {
+            if d == 0.0 {
+                if from >= 0.0 && from <= max {
+                    (f32::NEG_INFINITY, f32::INFINITY)
+                } else {
+                    (f32::INFINITY, f32::NEG_INFINITY)
+                }
+            } else {
+                let (t0, t1) = ((0.0 - from) / d, (max - from) / d);
+                (t0.min(t1), t0.max(t1))
+            }
+        };
+        let (tx0, tx1) = slab(from_loc.x, dir.x, grid_max_x);
+        let (ty0, ty1) = slab(from_loc.y, dir.y, grid_max_y);
+        let t_enter = tx0.max(ty0).max(0.0);
+        let t_exit = tx1.min(ty1).min(max_dist);
+        if t_enter > t_exit {
+            return;
+        }
+
+        let start = from_loc + dir * t_enter;
+        let mut col = ((start.x / self.cell_size).floor().max(0.0) as usize).min(self.cols - 1);
+        let mut row = ((start.y / self.cell_size).floor().max(0.0) as usize).min(self.rows - 1);
+
+        // distance along the ray to the next column/row boundary, and how far
+        // apart those boundaries are
+        let next_boundary = |cell: usize, from: f32, d: f32| {
+            if d > 0.0 {
+                ((cell + 1) as f32 * self.cell_size - from) / d
+            } else if d < 0.0 {
+                (cell as f32 * self.cell_size - from) / d
+            } else {
+                f32::INFINITY
+            }
+        };
+        let mut t_max_x = next_boundary(col, from_loc.x, dir.x);
+        let mut t_max_y = next_boundary(row, from_loc.y, dir.y);
+        let t_delta_x = self.cell_size / dir.x.abs();
+        let t_delta_y = self.cell_size / dir.y.abs();
+
+        loop {
+            let cell_exit = t_max_x.min(t_max_y).min(t_exit);
+            if visit(&self.cells[row * self.cols + col], cell_exit) || cell_exit >= t_exit {
+                break;
+            }
+
+            if t_max_x < t_max_y {
+                if (dir.x > 0.0 && col + 1 >= self.cols) || (dir.x < 0.0 && col == 0) {
+                    break;
+                }
+                col = if dir.x > 0.0 { col + 1 } else { col - 1 };
+                t_max_x += t_delta_x;
+            } else {
+                if (dir.y > 0.0 && row + 1 >= self.rows) || (dir.y < 0.0 && row == 0) {
+                    break;
+                }
+                row = if dir.y > 0.0 { row + 1 } else { row - 1 };
+                t_max_y += t_delta_y;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use robo_sim_utils::ray::*;
+
+    use super::super::environment::*;
+    use super::*;
+
+    fn random_env(num_shapes: usize, size: f32, rng: &mut StdRng) -> Environment {
+        let mut env = Environment::new(size, size);
+        for i in 0..num_shapes {
+            let (x, y) = (rng.gen_range(0.0..size), rng.gen_range(0.0..size));
+            match i % 3 {
+                0 => env.add_obstacle(Obstacle::new(x, y, rng.gen_range(0.1..1.0))),
+                1 => {
+                    let (dx, dy) = (rng.gen_range(-3.0..3.0), rng.gen_range(-3.0..3.0));
+                    env.add_wall(Wall::new(x, y, x + dx, y + dy));
+                }
+                _ => env.add_polygon(Polygon::new(vec![
+                    Vec3d::new(x, y, 0.0),
+                    Vec3d::new(x + 1.0, y, 0.0),
+                    Vec3d::new(x + 0.5, y + 1.0, 0.0),
+                ])),
+            }
+        }
+
+        env
+    }
+
+    fn random_ray(size: f32, rng: &mut StdRng) -> Ray<f32> {
+        let from = Vec3d::new(rng.gen_range(0.0..size), rng.gen_range(0.0..size), 0.0);
+        let angle = rng.gen_range(-std::f32::consts::PI..std::f32::consts::PI);
+        Ray::new(from, from + Vec3d::new(1.0, 0.0, 0.0).rotated_z(angle))
+    }
+
+    // what the simulator did before there was an index
+    fn linear_closest_hit(env: &Environment, ray: &Ray<f32>) -> Option<Vec3d<f32>> {
+        let obs_hits = env
+            .obstacles
+            .iter()
+            .filter_map(|obs| obs.intersect_with_ray(ray));
+        let wall_hits = env
+            .walls
+            .iter()
+            .filter_map(|wall| wall.intersect_with_ray(ray));
+        let polygon_hits = env
+            .polygons
+            .iter()
+            .filter_map(|polygon| polygon.intersect_with_ray(ray));
+
+        obs_hits
+            .chain(wall_hits)
+            .chain(polygon_hits)
+            .reduce(|accum, hit| {
+                if (accum - ray.from_loc).len_sq() <= (hit - ray.from_loc).len_sq() {
+                    accum
+                } else {
+                    hit
+                }
+            })
+    }
+
+    fn linear_first_collision(env: &Environment, ray: &Ray<f32>, radius: f32) -> Option<f32> {
+        let obs_hits = env
+            .obstacles
+            .iter()
+            .filter_map(|obs| obs.sweep_circle(ray, radius));
+        let wall_hits = env
+            .walls
+            .iter()
+            .filter_map(|wall| wall.sweep_circle(ray, radius));
+        let polygon_hits = env
+            .polygons
+            .iter()
+            .filter_map(|polygon| polygon.sweep_circle(ray, radius));
+
+        obs_hits
+            .chain(wall_hits)
+            .chain(polygon_hits)
+            .map(|hit| hit.t)
+            .reduce(f32::min)
+    }
+
+    #[test]
+    fn test_walk_ray_visits_cells_nearest_first() {
+        let mut index = SpatialIndex::new(10.0, 10.0, 2.0);
+        index.insert(
+            ShapeRef::Obstacle(0),
+            Vec3d::new(8.5, 0.5, 0.0),
+            Vec3d::new(9.5, 1.5, 0.0),
+        );
+
+        let mut cell_exits = vec![];
+        let mut found_at = None;
+        let (from, dir) = (Vec3d::new(1.0, 1.0, 0.0), Vec3d::new(1.0, 0.0, 0.0));
+        index.walk_ray(from, dir, f32::INFINITY, |shapes, cell_exit| {
+            cell_exits.push(cell_exit);
+            if !shapes.is_empty() {
+                found_at = Some(cell_exit);
+            }
+            false
+        });
+
+        assert_eq!(cell_exits, vec![1.0, 3.0, 5.0, 7.0, 9.0]);
+        assert_eq!(found_at, Some(9.0));
+
+        // starting outside the grid still walks the part inside it
+        let mut num_cells = 0;
+        let from = Vec3d::new(-5.0, 1.0, 0.0);
+        index.walk_ray(from, dir, f32::INFINITY, |_, _| {
+            num_cells += 1;
+            false
+        });
+        assert_eq!(num_cells, 5);
+    }
+
+    #[test]
+    fn test_index_matches_linear_scan() {
+        let mut rng = StdRng::seed_from_u64(13);
+        let mut env = random_env(300, 60.0, &mut rng);
+
+        // one shape hanging off the edge of the map still has to be found
+        env.add_obstacle(Obstacle::new(-1.0, 30.0, 2.0));
+
+        for _ in 0..500 {
+            let ray = random_ray(60.0, &mut rng);
+            assert_eq!(
+                env.closest_shape_hit(&ray, f32::INFINITY),
+                linear_closest_hit(&env, &ray)
+            );
+
+            let step = (ray.to_loc - ray.from_loc) * 0.8;
+            let move_ray = Ray::new(ray.from_loc, ray.from_loc + step);
+            assert_eq!(
+                env.first_shape_collision(&move_ray, 0.5).map(|hit| hit.t),
+                linear_first_collision(&env, &move_ray, 0.5)
+            );
+        }
+    }
+
+    // cargo test --release -p robo_sim_console bench_ -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_sensing_and_collision_10k_shapes() {
+        const NUM_RAYS: usize = 16 * 500;
+
+        let mut rng = StdRng::seed_from_u64(10_000);
+        let env = random_env(10_000, 500.0, &mut rng);
+        let rays: Vec<Ray<f32>> = (0..NUM_RAYS).map(|_| random_ray(500.0, &mut rng)).collect();
+
+        let start = Instant::now();
+        let linear_hits = rays
+            .iter()
+            .filter(|ray| linear_closest_hit(&env, ray).is_some())
+            .count();
+        let linear_sensing = start.elapsed();
+
+        let start = Instant::now();
+        let indexed_hits = rays
+            .iter()
+            .filter(|ray| env.closest_shape_hit(ray, f32::INFINITY).is_some())
+            .count();
+        let indexed_sensing = start.elapsed();
+        assert_eq!(linear_hits, indexed_hits);
+
+        let start = Instant::now();
+        for ray in &rays {
+            linear_first_collision(&env, ray, 0.5);
+        }
+        let linear_collision = start.elapsed();
+
+        let start = Instant::now();
+        for ray in &rays {
+            env.first_shape_collision(ray, 0.5);
+        }
+        let indexed_collision = start.elapsed();
+
+        println!(
+            "sensing, {} rays: linear {:?}, indexed {:?} ({:.1}x)",
+            NUM_RAYS,
+            linear_sensing,
+            indexed_sensing,
+            linear_sensing.as_secs_f64() / indexed_sensing.as_secs_f64()
+        );
+        println!(
+            "collision, {} moves: linear {:?}, indexed {:?} ({:.1}x)",
+            NUM_RAYS,
+            linear_collision,
+            indexed_collision,
+            linear_collision.as_secs_f64() / indexed_collision.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,19 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// State shared by all of a robot's behaviors. Anything stochastic draws from
+/// `rng` rather than the thread-local generator, so a run can be repeated
+/// exactly by giving the robot the same seed.
+pub struct BehaviorContext {
+    pub seed: u64,
+    pub rng: StdRng,
+}
+
+impl BehaviorContext {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed: seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
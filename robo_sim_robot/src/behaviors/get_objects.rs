@@ -0,0 +1,84 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use robo_sim_utils::object_reading::ObjectReading;
+
+use super::super::robot_interfaces::robot_interface::*;
+
+use super::behavior::*;
+
+pub struct GetObjects {
+    pub name: String,
+    pub cycle: u64,
+    pub robot_interface: Rc<RefCell<dyn RobotInterface>>,
+    pub max_range_input: Rc<RefCell<dyn Behavior>>,
+    pub fov_input: Rc<RefCell<dyn Behavior>>,
+    pub readings: Vec<ObjectReading>,
+}
+
+impl GetObjects {
+    pub fn new(
+        name: Option<&str>,
+        robot_interface: Rc<RefCell<dyn RobotInterface>>,
+        max_range_input: Rc<RefCell<dyn Behavior>>,
+        fov_input: Rc<RefCell<dyn Behavior>>,
+    ) -> Self {
+        Self {
+            name: get_behavior_name(name),
+            cycle: 0,
+            robot_interface: robot_interface,
+            max_range_input: max_range_input,
+            fov_input: fov_input,
+            readings: vec![],
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_output(&mut self, cycle: u64) -> &dyn Any {
+        if cycle == self.cycle {
+            return &self.readings;
+        }
+        self.cycle = cycle;
+
+        let mut max_range_input_mut = self.max_range_input.borrow_mut();
+        let mut fov_input_mut = self.fov_input.borrow_mut();
+
+        let max_range = *downcast_input::<f32>(
+            max_range_input_mut.get_output(cycle),
+            "GetObjects",
+            "max_range_input",
+        );
+
+        let fov = *downcast_input::<f32>(
+            fov_input_mut.get_output(cycle),
+            "GetObjects",
+            "fov_input",
+        );
+
+        // the console not answering leaves the last readings in place
+        match self
+            .robot_interface
+            .borrow()
+            .get_object_readings(max_range, fov)
+        {
+            Ok(readings) => self.readings = readings,
+            Err(err) => println!("Error: {}: {}", self.name, err),
+        }
+
+        &self.readings
+    }
+}
+
+impl Behavior for GetObjects {
+    fn get_name(&self) -> &str {
+        GetObjects::get_name(self)
+    }
+
+    fn get_output(&mut self, cycle: u64) -> &dyn Any {
+        GetObjects::get_output(self, cycle)
+    }
+}
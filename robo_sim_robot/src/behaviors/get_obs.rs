@@ -31,7 +31,11 @@ impl GetObs {
 
     pub fn get_output(&mut self, cycle: u64) -> &dyn Any {
         if cycle != self.cycle {
-            self.readings = self.robot_interface.borrow().get_obs_readings();
+            // the console not answering leaves the last readings in place
+            match self.robot_interface.borrow().get_obs_readings() {
+                Ok(readings) => self.readings = readings,
+                Err(err) => println!("Error: {}: {}", self.name, err),
+            }
             self.cycle = cycle;
         }
 
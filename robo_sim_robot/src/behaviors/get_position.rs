@@ -31,7 +31,11 @@ impl GetPosition {
 
     pub fn get_output(&mut self, cycle: u64) -> &dyn Any {
         if cycle != self.cycle {
-            self.pos = self.robot_interface.borrow().get_position();
+            // the console not answering leaves the last position in place
+            match self.robot_interface.borrow().get_position() {
+                Ok(pos) => self.pos = pos,
+                Err(err) => println!("Error: {}: {}", self.name, err),
+            }
             self.cycle = cycle;
         }
 
@@ -8,6 +8,7 @@ use super::super::robot_interfaces::robot_interface::*;
 
 use super::avoid_obs::*;
 use super::behavior::*;
+use super::behavior_context::*;
 use super::get_obs::*;
 use super::get_position::*;
 use super::global_to_egocentric::*;
@@ -26,7 +27,11 @@ pub struct TestGoto {
 }
 
 impl TestGoto {
-    pub fn new(name: Option<&str>, robot_interface: Rc<RefCell<dyn RobotInterface>>) -> Self {
+    pub fn new(
+        name: Option<&str>,
+        robot_interface: Rc<RefCell<dyn RobotInterface>>,
+        context: Rc<RefCell<BehaviorContext>>,
+    ) -> Self {
         let get_pos = Rc::new(RefCell::new(GetPosition::new(
             None,
             robot_interface.clone(),
@@ -57,6 +62,7 @@ impl TestGoto {
 
         let wander = Rc::new(RefCell::new(Wander::new(
             None,
+            context,
             Rc::new(RefCell::new(LiteralF32::new(None, 10f32))),
         )));
 
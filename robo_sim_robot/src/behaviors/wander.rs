@@ -3,25 +3,32 @@ use std::cell::RefCell;
 use std::f32::consts::PI;
 use std::rc::Rc;
 
-use rand;
+use rand::Rng;
 
 use robo_sim_utils::vec3d::*;
 
 use super::behavior::*;
+use super::behavior_context::*;
 
 pub struct Wander {
     pub name: String,
     pub cycle: u64,
+    pub context: Rc<RefCell<BehaviorContext>>,
     pub persistence_input: Rc<RefCell<dyn Behavior>>,
     pub same_direction_count: u32,
     pub output: Vec3d<f32>,
 }
 
 impl<'a> Wander {
-    pub fn new(name: Option<&str>, persistence_input: Rc<RefCell<dyn Behavior>>) -> Self {
+    pub fn new(
+        name: Option<&str>,
+        context: Rc<RefCell<BehaviorContext>>,
+        persistence_input: Rc<RefCell<dyn Behavior>>,
+    ) -> Self {
         Self {
             name: get_behavior_name(name),
             cycle: 0,
+            context: context,
             persistence_input: persistence_input,
             same_direction_count: 0,
             output: Default::default(),
@@ -50,7 +57,7 @@ impl<'a> Wander {
         }
 
         if self.same_direction_count == 0 {
-            let theta = rand::random::<f32>() * 2.0 * PI;
+            let theta = self.context.borrow_mut().rng.gen::<f32>() * 2.0 * PI;
             self.output = Vec3d::new(1.0, 0.0, 0.0).rotated_z(theta);
         }
 
@@ -10,6 +10,9 @@ pub struct Controller {
     behaviors: Vec<Rc<RefCell<dyn Behavior>>>,
     paused: bool,
     cycle: u64,
+    // in lockstep mode, behaviors only run once per tick from the console
+    lockstep: bool,
+    pending_tick: Option<u64>,
 }
 
 impl Controller {
@@ -18,6 +21,8 @@ impl Controller {
             behaviors: Vec::new(),
             paused: true,
             cycle: 0,
+            lockstep: false,
+            pending_tick: None,
         }
     }
 
@@ -40,12 +45,22 @@ impl Controller {
                 continue;
             }
 
+            let tick = self.pending_tick.take();
+            if self.lockstep && tick.is_none() {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                continue;
+            }
+
             // run all the top-level behaviors
             for behavior in &self.behaviors {
                 behavior.borrow_mut().get_output(self.cycle);
             }
 
             self.cycle += 1;
+
+            if let Some(tick) = tick {
+                comm.borrow().send_tick_done(tick);
+            }
         }
     }
 
@@ -54,13 +69,16 @@ impl Controller {
         let msgs = comm.check_msgs(None);
         for msg in msgs {
             match msg.get_type() {
-                MessageType::Start => self.handle_start(),
+                MessageType::Start => self.handle_start(&msg),
                 MessageType::Kill => {
                     self.handle_kill(comm);
                     got_kill = true;
                     break;
                 }
                 MessageType::Pause => self.handle_pause(),
+                MessageType::GoalEvent => self.handle_goal_event(&msg),
+                MessageType::Tick => self.handle_tick(&msg),
+                MessageType::Position => self.handle_position(&msg),
                 _ => println!("unhandled message type: {}", msg.get_type() as i32),
             }
         }
@@ -68,9 +86,26 @@ impl Controller {
         got_kill
     }
 
-    fn handle_start(&mut self) {
+    fn handle_start(&mut self, msg: &Box<dyn Message>) {
+        let msg = downcast::<StartMsg>(msg, "handle_start");
         self.paused = false;
-        println!("robot got START message");
+        self.lockstep = msg.lockstep;
+        println!("robot got START message (lockstep: {})", self.lockstep);
+    }
+
+    fn handle_tick(&mut self, msg: &Box<dyn Message>) {
+        let msg = downcast::<TickMsg>(msg, "handle_tick");
+        self.pending_tick = Some(msg.tick);
+    }
+
+    fn handle_position(&mut self, msg: &Box<dyn Message>) {
+        // the console only sends one unasked when the robot's been dragged;
+        // behaviors pick up the new position on their next request
+        let msg = downcast::<PositionMsg>(msg, "handle_position");
+        println!(
+            "robot moved to x={:.3} y={:.3}",
+            msg.pos.location.x, msg.pos.location.y
+        );
     }
 
     fn handle_kill(&mut self, comm: &mut RobotComm) {
@@ -80,4 +115,10 @@ impl Controller {
     fn handle_pause(&mut self) {
         self.paused = !self.paused;
     }
+
+    fn handle_goal_event(&mut self, msg: &Box<dyn Message>) {
+        let msg = downcast::<GoalEventMsg>(msg, "handle_goal_event");
+        let action = if msg.entered { "reached" } else { "left" };
+        println!("robot {} goal {} after {:.2}s", action, msg.goal_id, msg.elapsed_s);
+    }
 }
@@ -7,6 +7,7 @@ use std::rc::Rc;
 use getopts::Options;
 
 use robo_sim_utils::color::Color;
+use robo_sim_utils::sensor_config::SensorConfig;
 use robo_sim_utils::TO_RAD_F32;
 
 use behaviors::test_goto::TestGoto;
@@ -24,6 +25,8 @@ pub mod robot_interfaces {
 pub mod behaviors {
     pub mod avoid_obs;
     pub mod behavior;
+    pub mod behavior_context;
+    pub mod get_objects;
     pub mod get_obs;
     pub mod get_position;
     pub mod global_to_egocentric;
@@ -55,6 +58,13 @@ fn main() {
     opts.optopt("v", "", "max velocity", "MAX VEL");
     opts.optopt("a", "", "max angular velocity", "MAX ANGULAR VEL");
     opts.optopt("r", "", "radius", "RADIUS");
+    opts.optopt("", "sensor-rays", "number of obstacle sensor rays", "COUNT");
+    opts.optopt("", "sensor-fov", "obstacle sensor field of view", "DEGREES");
+    opts.optopt("", "sensor-range", "obstacle sensor max range", "METERS");
+    opts.optopt("", "sensor-offset", "obstacle sensor angular offset", "DEGREES");
+    opts.optopt("", "sensor-noise", "obstacle sensor range noise std dev", "METERS");
+    opts.optopt("", "sensor-dropout", "obstacle sensor dropout probability", "PROB");
+    opts.optopt("", "seed", "seed for the behaviors' random numbers", "SEED");
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
         Err(f) => {
@@ -72,6 +82,8 @@ fn main() {
     let mut max_vel = 0f32;
     let mut max_angular_vel = 0f32;
     let mut radius = 0.5f32;
+    let mut sensor_config = SensorConfig::default();
+    let mut seed = rand::random::<u64>();
 
     if let Some(id_opt) = matches.opt_str("i") {
         id = id_opt.parse::<u32>().unwrap(); // ok to panic
@@ -115,6 +127,36 @@ fn main() {
         radius = radius_opt.parse::<f32>().unwrap(); // ok to panic
     }
 
+    if let Some(rays_opt) = matches.opt_str("sensor-rays") {
+        sensor_config.num_rays = rays_opt.parse::<u32>().unwrap(); // ok to panic
+    }
+
+    if let Some(fov_opt) = matches.opt_str("sensor-fov") {
+        sensor_config.fov_rad = fov_opt.parse::<f32>().unwrap(); // ok to panic
+        sensor_config.fov_rad *= TO_RAD_F32;
+    }
+
+    if let Some(range_opt) = matches.opt_str("sensor-range") {
+        sensor_config.max_range = range_opt.parse::<f32>().unwrap(); // ok to panic
+    }
+
+    if let Some(offset_opt) = matches.opt_str("sensor-offset") {
+        sensor_config.angle_offset_rad = offset_opt.parse::<f32>().unwrap(); // ok to panic
+        sensor_config.angle_offset_rad *= TO_RAD_F32;
+    }
+
+    if let Some(noise_opt) = matches.opt_str("sensor-noise") {
+        sensor_config.range_noise_std_dev = noise_opt.parse::<f32>().unwrap(); // ok to panic
+    }
+
+    if let Some(dropout_opt) = matches.opt_str("sensor-dropout") {
+        sensor_config.dropout_prob = dropout_opt.parse::<f32>().unwrap(); // ok to panic
+    }
+
+    if let Some(seed_opt) = matches.opt_str("seed") {
+        seed = seed_opt.parse::<u64>().unwrap(); // ok to panic
+    }
+
     let mut r = Robot::new(
         id,
         &host,
@@ -127,15 +169,18 @@ fn main() {
         max_vel,
         max_angular_vel,
         radius,
+        sensor_config,
+        seed,
     );
 
     let test_goto = Rc::new(RefCell::new(TestGoto::new(
         Some("TestGoto"),
         r.get_robot_interface(),
+        r.get_behavior_context(),
     )));
 
     r.add_behavior(test_goto);
-    println!("Robot starting with ID {}", id);
+    println!("Robot starting with ID {} (seed {})", id, seed);
     r.run();
 
     // let l1 = behaviors::literal::LiteralF32::new(Some("a"), 1.0);
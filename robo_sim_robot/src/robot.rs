@@ -3,9 +3,11 @@ use std::rc::Rc;
 
 use robo_sim_utils::color::*;
 use robo_sim_utils::robot_position::*;
+use robo_sim_utils::sensor_config::*;
 use robo_sim_utils::vec3d::*;
 
 use super::behaviors::behavior::Behavior;
+use super::behaviors::behavior_context::BehaviorContext;
 use super::controller::Controller;
 use super::robot_comm::RobotComm;
 use super::robot_interfaces::robot_interface::*;
@@ -15,6 +17,7 @@ pub struct Robot {
     comm: Rc<RefCell<RobotComm>>,
     controller: Rc<RefCell<Controller>>,
     robot_interface: Rc<RefCell<dyn RobotInterface>>,
+    behavior_context: Rc<RefCell<BehaviorContext>>,
 }
 
 impl Robot {
@@ -30,6 +33,8 @@ impl Robot {
         max_vel: f32,
         max_angular_vel: f32,
         radius: f32,
+        sensor_config: SensorConfig,
+        seed: u64,
     ) -> Self {
         let comm = Rc::new(RefCell::new(RobotComm::new(host, id)));
 
@@ -46,6 +51,7 @@ impl Robot {
 
         comm.borrow_mut()
             .send_alive_confirmation(pos, color, max_vel, max_angular_vel, radius);
+        comm.borrow().send_sensor_config(sensor_config);
 
         let controller = Rc::new(RefCell::new(Controller::new()));
 
@@ -53,6 +59,7 @@ impl Robot {
             comm: comm,
             controller: controller,
             robot_interface: robot_interface,
+            behavior_context: Rc::new(RefCell::new(BehaviorContext::new(seed))),
         }
     }
 
@@ -60,6 +67,10 @@ impl Robot {
         self.robot_interface.clone()
     }
 
+    pub fn get_behavior_context(&self) -> Rc<RefCell<BehaviorContext>> {
+        self.behavior_context.clone()
+    }
+
     pub fn add_behavior(&mut self, behavior: Rc<RefCell<dyn Behavior>>) {
         self.controller.borrow_mut().add_behavior(behavior);
     }
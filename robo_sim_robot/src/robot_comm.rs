@@ -1,9 +1,13 @@
+use std::cell::Cell;
 use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
 
 use robo_sim_utils::color::*;
-use robo_sim_utils::comms;
+use robo_sim_utils::comms::{self, CommsError, WireMessage};
 use robo_sim_utils::messages::*;
+use robo_sim_utils::object_reading::*;
 use robo_sim_utils::robot_position::*;
+use robo_sim_utils::sensor_config::*;
 use robo_sim_utils::vec3d::*;
 
 //#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
@@ -13,9 +17,21 @@ pub struct RobotComm {
     pub queued_msgs: Vec<Box<dyn Message>>,
     pub sock: Option<UdpSocket>,
     pub console_addr: Option<SocketAddr>,
+    // sequence number for the next message sent
+    next_seq: Cell<u32>,
+    reassembler: comms::Reassembler,
+    // simulated time from the latest position reply
+    pub last_sim_time: f64,
+    // how long to wait for each reply, and how many times a request gets
+    // re-sent before giving up on it
+    pub request_timeout: Duration,
+    pub max_retries: u32,
 }
 
 impl RobotComm {
+    pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_millis(250);
+    pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
     pub fn new(host: &str, id: u32) -> Self {
         Self {
             host: host.to_string(),
@@ -23,6 +39,11 @@ impl RobotComm {
             queued_msgs: Vec::new(),
             sock: None,
             console_addr: None,
+            next_seq: Cell::new(1),
+            reassembler: comms::Reassembler::new(),
+            last_sim_time: 0.0,
+            request_timeout: RobotComm::DEFAULT_REQUEST_TIMEOUT,
+            max_retries: RobotComm::DEFAULT_MAX_RETRIES,
         }
     }
 
@@ -37,21 +58,27 @@ impl RobotComm {
         let console_addr_str = format!("{}:{}", self.host, comms::CONSOLE_PORT);
         let console_addr = console_addr_str
             .to_socket_addrs()?
-            .filter(|sock_addr| match sock_addr { SocketAddr::V4(_) => true, _ => false })
+            .filter(|sock_addr| match sock_addr {
+                SocketAddr::V4(_) => true,
+                _ => false,
+            })
             .next()
             .ok_or_else(|| {
                 std::io::Error::new(
                     std::io::ErrorKind::Other,
                     format!("Could not convert '{}' to sockaddr.", console_addr_str),
                 )
-        })?;
+            })?;
         println!("robot opening socket to console: {}", console_addr);
         self.console_addr = Some(console_addr);
 
         Ok(())
     }
 
-    pub fn check_msgs(&mut self, wait_for: Option<MessageType>) -> Vec<Box<dyn Message>> {
+    /// Returns whatever's come in from the console. When waiting on the reply
+    /// to a request (its type and the request's seq), returns as soon as it
+    /// shows up and holds everything else for later.
+    pub fn check_msgs(&mut self, wait_for: Option<(MessageType, u32)>) -> Vec<Box<dyn Message>> {
         let mut msgs: Vec<Box<dyn Message>> = vec![];
 
         // make sure we the socket has been opened
@@ -68,24 +95,43 @@ impl RobotComm {
 
         // keep going while there are messages waiting
         loop {
-            let mut buf = [0; 1024];
+            let mut buf = [0; comms::MAX_DATAGRAM_LEN];
             match sock.recv_from(&mut buf) {
                 Ok((received, _)) => {
-                    let msg = comms::parse_message(&buf[..received]).unwrap();
-                    if wait_for == Some(msg.get_type()) {
-                        msgs.push(msg);
-                        return msgs;
-                    } else if wait_for.is_some() {
-                        self.queued_msgs.push(msg);
-                    } else {
-                        msgs.push(msg);
+                    let parsed = match self.reassembler.add(&buf[..received]) {
+                        Ok(Some(whole_buf)) => comms::parse_message(&whole_buf),
+                        Ok(None) => continue,
+                        Err(err) => Err(err),
+                    };
+                    let (header, msg) = match parsed {
+                        Ok(parsed) => parsed,
+                        Err(err) => {
+                            println!("Error: dropping msg from the console: {}", err);
+                            continue;
+                        }
+                    };
+                    match header.reply_to {
+                        Some(request_seq) if wait_for == Some((msg.get_type(), request_seq)) => {
+                            msgs.push(msg);
+                            return msgs;
+                        }
+                        // the answer to a request that's already been
+                        // answered or given up on
+                        Some(request_seq) => {
+                            println!("dropping late reply to request {}", request_seq);
+                        }
+                        None if wait_for.is_some() => self.queued_msgs.push(msg),
+                        None => msgs.push(msg),
                     }
                 }
                 Err(e) => {
                     match e.kind() {
                         std::io::ErrorKind::WouldBlock => break,
                         std::io::ErrorKind::ConnectionReset => break,
-                        _ => panic!("recv function failed: {:?}", e),
+                        _ => {
+                            println!("Error: {}", CommsError::from(e));
+                            break;
+                        }
                     };
                 }
             }
@@ -94,22 +140,86 @@ impl RobotComm {
         msgs
     }
 
-    pub fn wait_for_msg(&mut self, wait_for: MessageType) -> Box<dyn Message> {
-        let sleep_time = std::time::Duration::from_millis(10);
+    fn wait_for_reply(
+        &mut self,
+        reply_type: MessageType,
+        request_seq: u32,
+    ) -> Option<Box<dyn Message>> {
+        let sleep_time = Duration::from_millis(10);
+        let started_at = Instant::now();
         loop {
-            let mut msgs = self.check_msgs(Some(wait_for));
+            let mut msgs = self.check_msgs(Some((reply_type, request_seq)));
             if !msgs.is_empty() {
-                return msgs.pop().unwrap();
+                return msgs.pop();
+            }
+            if started_at.elapsed() >= self.request_timeout {
+                return None;
             }
             std::thread::sleep(sleep_time);
         }
     }
 
-    fn send_msg(&self, msg_buf: &[u8]) {
-        let sock = self.sock.as_ref().unwrap();
-        let console_addr = self.console_addr.as_ref().unwrap();
-        if let Err(err) = sock.send_to(msg_buf, console_addr) {
-            panic!("send_msg failed to send to addr {:?}: err={:?}", console_addr, err);
+    /// Sends a request and waits for its reply, re-sending it each time the
+    /// wait runs out. Every attempt carries the same seq, so a slow reply to
+    /// an earlier attempt still counts.
+    fn request<M: WireMessage>(
+        &mut self,
+        msg: M,
+        reply_type: MessageType,
+    ) -> Result<Box<dyn Message>, CommsError> {
+        let msg_buf = msg.pack();
+        let request_seq = self.take_seq();
+        let attempts = self.max_retries + 1;
+        for attempt in 1..=attempts {
+            self.send_with_seq(&msg_buf, request_seq)?;
+            if let Some(reply) = self.wait_for_reply(reply_type, request_seq) {
+                return Ok(reply);
+            }
+            if attempt < attempts {
+                println!(
+                    "no reply to {:?} request {}, re-sending",
+                    M::MSG_TYPE,
+                    request_seq
+                );
+            }
+        }
+
+        Err(CommsError::NoReply {
+            request: M::MSG_TYPE,
+            attempts: attempts,
+        })
+    }
+
+    fn take_seq(&self) -> u32 {
+        let seq = self.next_seq.get();
+        self.next_seq.set(comms::next_seq(seq));
+        seq
+    }
+
+    fn send_msg(&self, msg_buf: &[u8]) -> Result<(), CommsError> {
+        self.send_with_seq(msg_buf, self.take_seq())
+    }
+
+    fn send_with_seq(&self, msg_buf: &[u8], seq: u32) -> Result<(), CommsError> {
+        let (sock, console_addr) = match (self.sock.as_ref(), self.console_addr.as_ref()) {
+            (Some(sock), Some(console_addr)) => (sock, console_addr),
+            _ => {
+                let err = std::io::Error::new(std::io::ErrorKind::NotConnected, "comms not open");
+                return Err(CommsError::Socket(err));
+            }
+        };
+
+        let header = comms::MsgHeader::new(self.id, seq, None, comms::timestamp_now_ms());
+        for datagram in comms::fragment_message(header, msg_buf)? {
+            sock.send_to(&datagram, console_addr)?;
+        }
+        Ok(())
+    }
+
+    // a message that can't be sent is dropped, same as UDP would
+    fn send_or_log(&self, msg_buf: &[u8]) {
+        if let Err(err) = self.send_msg(msg_buf) {
+            println!("Error: could not send to the console: {}", err);
         }
     }
 
@@ -126,50 +236,210 @@ impl RobotComm {
         }
 
         let msg = AliveMsg::new(self.id, pos, color, max_vel, max_angular_vel, radius);
-        let msg_buf = comms::pack_alive_message(msg);
-        self.send_msg(msg_buf.as_slice());
+        let msg_buf = msg.pack();
+        self.send_or_log(msg_buf.as_slice());
+    }
+
+    pub fn send_sensor_config(&self, config: SensorConfig) {
+        let msg = SensorConfigMsg::new(self.id, config);
+        let msg_buf = msg.pack();
+        self.send_or_log(msg_buf.as_slice());
     }
 
     pub fn send_position_update(&self, pos: RobotPosition) {
-        let msg = PositionMsg::new(self.id, pos);
-        let msg_buf = comms::pack_position_message(msg);
-        self.send_msg(msg_buf.as_slice());
+        let msg = PositionMsg::new(self.id, pos, self.last_sim_time);
+        let msg_buf = msg.pack();
+        self.send_or_log(msg_buf.as_slice());
     }
 
-    pub fn get_position(&mut self) -> RobotPosition {
+    pub fn get_position(&mut self) -> Result<RobotPosition, CommsError> {
         let req_msg = RequestPositionMsg::new(self.id);
-        let req_msg_buf = comms::pack_request_position_message(req_msg);
-        self.send_msg(req_msg_buf.as_slice());
-        let reply_msg = self.wait_for_msg(MessageType::Position);
+        let reply_msg = self.request(req_msg, MessageType::Position)?;
         let pos_msg = downcast::<PositionMsg>(&reply_msg, "get_position");
-        pos_msg.pos
+        self.last_sim_time = pos_msg.sim_time;
+        Ok(pos_msg.pos)
+    }
+
+    pub fn send_tick_done(&self, tick: u64) {
+        let msg = TickDoneMsg::new(self.id, tick);
+        let msg_buf = msg.pack();
+        self.send_or_log(msg_buf.as_slice());
     }
 
     pub fn send_death_msg(&self) {
         let msg = RobotDyingMsg::new(self.id);
-        let msg_buf = comms::pack_robot_dying_message(msg);
-        self.send_msg(msg_buf.as_slice());
+        let msg_buf = msg.pack();
+        self.send_or_log(msg_buf.as_slice());
     }
 
-    pub fn get_obs(&mut self) -> Vec<Vec3d<f32>> {
+    pub fn get_obs(&mut self) -> Result<Vec<Vec3d<f32>>, CommsError> {
         let req_msg = GetObstaclesMsg::new(self.id);
-        let req_msg_buf = comms::pack_get_obstacles_message(req_msg);
-        self.send_msg(req_msg_buf.as_slice());
-        let mut reply_msg = self.wait_for_msg(MessageType::ObsReadings);
+        let mut reply_msg = self.request(req_msg, MessageType::ObsReadings)?;
         let obs_msg = downcast_mut::<ObsReadingsMsg>(&mut reply_msg, "get_obs");
 
-        std::mem::take(&mut obs_msg.readings)
+        Ok(std::mem::take(&mut obs_msg.readings))
+    }
+
+    pub fn get_objects(
+        &mut self,
+        max_range: f32,
+        fov_rad: f32,
+    ) -> Result<Vec<ObjectReading>, CommsError> {
+        let req_msg = GetObjectsMsg::new(self.id, max_range, fov_rad);
+        let mut reply_msg = self.request(req_msg, MessageType::ObjectReadings)?;
+        let objects_msg = downcast_mut::<ObjectReadingsMsg>(&mut reply_msg, "get_objects");
+
+        Ok(std::mem::take(&mut objects_msg.readings))
+    }
+
+    // picking up and dropping are safe to re-send: asking twice has the same
+    // effect as asking once
+
+    pub fn pickup(&mut self) -> Result<bool, CommsError> {
+        let req_msg = PickupMsg::new(self.id);
+        let reply_msg = self.request(req_msg, MessageType::CarryStatus)?;
+        let status_msg = downcast::<CarryStatusMsg>(&reply_msg, "pickup");
+        Ok(status_msg.carrying)
+    }
+
+    pub fn drop_object(&mut self) -> Result<bool, CommsError> {
+        let req_msg = DropMsg::new(self.id);
+        let reply_msg = self.request(req_msg, MessageType::CarryStatus)?;
+        let status_msg = downcast::<CarryStatusMsg>(&reply_msg, "drop_object");
+        Ok(status_msg.carrying)
     }
 
     pub fn sim_move(&self, x: f32, y: f32) {
         let msg = MoveMsg::new(self.id, x, y);
-        let msg_buf = comms::pack_move_message(msg);
-        self.send_msg(msg_buf.as_slice());
+        let msg_buf = msg.pack();
+        self.send_or_log(msg_buf.as_slice());
     }
 
     pub fn sim_spin(&self, theta: f32) {
         let msg = SpinMsg::new(self.id, theta);
-        let msg_buf = comms::pack_spin_message(msg);
-        self.send_msg(msg_buf.as_slice());
+        let msg_buf = msg.pack();
+        self.send_or_log(msg_buf.as_slice());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    // a robot pointed at a socket standing in for the console
+    fn comm_with_console() -> (RobotComm, UdpSocket) {
+        let console = UdpSocket::bind("127.0.0.1:0").unwrap();
+        console
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        let sock = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sock.set_nonblocking(true).unwrap();
+
+        let mut comm = RobotComm::new("127.0.0.1", 1);
+        comm.sock = Some(sock);
+        comm.console_addr = Some(console.local_addr().unwrap());
+        comm.request_timeout = Duration::from_millis(50);
+        comm.max_retries = 2;
+        (comm, console)
+    }
+
+    // the next request the console gets, with its seq and where it came from
+    fn recv_request(console: &UdpSocket) -> Option<(u32, SocketAddr)> {
+        let mut buf = [0; comms::MAX_DATAGRAM_LEN];
+        let (len, addr) = console.recv_from(&mut buf).ok()?;
+        let (header, _) = comms::parse_message(&buf[..len]).unwrap();
+        Some((header.seq, addr))
+    }
+
+    fn send<M: WireMessage>(console: &UdpSocket, addr: SocketAddr, msg: M, reply_to: Option<u32>) {
+        let header = comms::MsgHeader::new(comms::CONSOLE_ID, 1, reply_to, 0);
+        for datagram in comms::fragment_message(header, &msg.pack()).unwrap() {
+            console.send_to(&datagram, addr).unwrap();
+        }
+    }
+
+    fn position(x: f32) -> RobotPosition {
+        RobotPosition::new(Vec3d::new(x, 0.0, 0.0), 0.0)
+    }
+
+    #[test]
+    fn test_request_is_resent() {
+        let (mut comm, console) = comm_with_console();
+        let console_thread = thread::spawn(move || {
+            // the first attempt goes missing, and the retry carries the same
+            // seq
+            let (first_seq, _) = recv_request(&console).unwrap();
+            let (seq, addr) = recv_request(&console).unwrap();
+            assert_eq!(seq, first_seq);
+            send(
+                &console,
+                addr,
+                PositionMsg::new(1, position(2.0), 1.5),
+                Some(seq),
+            );
+        });
+
+        assert_eq!(comm.get_position().unwrap(), position(2.0));
+        assert_eq!(comm.last_sim_time, 1.5);
+        console_thread.join().unwrap();
+    }
+
+    #[test]
+    fn test_late_replies_are_dropped() {
+        let (mut comm, console) = comm_with_console();
+        comm.next_seq.set(5);
+        let console_thread = thread::spawn(move || {
+            let (seq, addr) = recv_request(&console).unwrap();
+            // an answer to some earlier request, then something unasked for,
+            // then the real answer
+            send(
+                &console,
+                addr,
+                PositionMsg::new(1, position(1.0), 0.5),
+                Some(seq - 1),
+            );
+            send(&console, addr, KillMsg::new(), None);
+            send(
+                &console,
+                addr,
+                PositionMsg::new(1, position(2.0), 1.0),
+                Some(seq),
+            );
+        });
+
+        assert_eq!(comm.get_position().unwrap(), position(2.0));
+        console_thread.join().unwrap();
+
+        // the kill was held onto rather than lost
+        let msgs = comm.check_msgs(None);
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].get_type(), MessageType::Kill);
+    }
+
+    #[test]
+    fn test_gives_up_without_a_reply() {
+        let (mut comm, console) = comm_with_console();
+        let console_thread = thread::spawn(move || {
+            let mut seqs = vec![];
+            while let Some((seq, _)) = recv_request(&console) {
+                seqs.push(seq);
+            }
+            seqs
+        });
+
+        let err = comm.get_obs().unwrap_err();
+        assert!(matches!(
+            err,
+            CommsError::NoReply {
+                request: MessageType::GetObstacles,
+                attempts: 3,
+            }
+        ));
+
+        let seqs = console_thread.join().unwrap();
+        assert_eq!(seqs.len(), 3);
+        assert!(seqs.iter().all(|seq| *seq == seqs[0]));
     }
 }
@@ -1,8 +1,24 @@
+use robo_sim_utils::comms::CommsError;
+use robo_sim_utils::object_reading::ObjectReading;
 use robo_sim_utils::robot_position::RobotPosition;
 use robo_sim_utils::vec3d::Vec3d;
 
+// anything that has to hear back from the console can fail, if it never
+// answers
 pub trait RobotInterface {
-    fn get_position(&self) -> RobotPosition;
+    fn get_position(&self) -> Result<RobotPosition, CommsError>;
+    // simulated time as of the last get_position call
+    fn get_sim_time(&self) -> f64;
     fn cmd_move(&self, x: f32, y: f32);
-    fn get_obs_readings(&self) -> Vec<Vec3d<f32>>;
+    fn cmd_spin(&self, theta: f32);
+    fn get_obs_readings(&self) -> Result<Vec<Vec3d<f32>>, CommsError>;
+    fn get_object_readings(
+        &self,
+        max_range: f32,
+        fov_rad: f32,
+    ) -> Result<Vec<ObjectReading>, CommsError>;
+
+    // both return whether the robot is carrying an object afterward
+    fn cmd_pickup(&self) -> Result<bool, CommsError>;
+    fn cmd_drop(&self) -> Result<bool, CommsError>;
 }
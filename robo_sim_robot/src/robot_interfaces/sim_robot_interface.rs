@@ -1,6 +1,8 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use robo_sim_utils::comms::CommsError;
+use robo_sim_utils::object_reading::*;
 use robo_sim_utils::robot_position::*;
 use robo_sim_utils::vec3d::*;
 
@@ -16,29 +18,77 @@ impl SimRobotInterface {
         Self { comm: comm }
     }
 
-    pub fn get_position(&self) -> RobotPosition {
+    pub fn get_position(&self) -> Result<RobotPosition, CommsError> {
         self.comm.borrow_mut().get_position()
     }
 
+    pub fn get_sim_time(&self) -> f64 {
+        self.comm.borrow().last_sim_time
+    }
+
     pub fn cmd_move(&self, x: f32, y: f32) {
         self.comm.borrow().sim_move(x, y)
     }
 
-    pub fn get_obs_readings(&self) -> Vec<Vec3d<f32>> {
+    pub fn cmd_spin(&self, theta: f32) {
+        self.comm.borrow().sim_spin(theta)
+    }
+
+    pub fn get_obs_readings(&self) -> Result<Vec<Vec3d<f32>>, CommsError> {
         self.comm.borrow_mut().get_obs()
     }
+
+    pub fn get_object_readings(
+        &self,
+        max_range: f32,
+        fov_rad: f32,
+    ) -> Result<Vec<ObjectReading>, CommsError> {
+        self.comm.borrow_mut().get_objects(max_range, fov_rad)
+    }
+
+    pub fn cmd_pickup(&self) -> Result<bool, CommsError> {
+        self.comm.borrow_mut().pickup()
+    }
+
+    pub fn cmd_drop(&self) -> Result<bool, CommsError> {
+        self.comm.borrow_mut().drop_object()
+    }
 }
 
 impl RobotInterface for SimRobotInterface {
-    fn get_position(&self) -> RobotPosition {
+    fn get_position(&self) -> Result<RobotPosition, CommsError> {
         SimRobotInterface::get_position(self)
     }
 
+    fn get_sim_time(&self) -> f64 {
+        SimRobotInterface::get_sim_time(self)
+    }
+
     fn cmd_move(&self, x: f32, y: f32) {
         SimRobotInterface::cmd_move(self, x, y)
     }
 
-    fn get_obs_readings(&self) -> Vec<Vec3d<f32>> {
+    fn cmd_spin(&self, theta: f32) {
+        SimRobotInterface::cmd_spin(self, theta)
+    }
+
+    fn get_obs_readings(&self) -> Result<Vec<Vec3d<f32>>, CommsError> {
         SimRobotInterface::get_obs_readings(self)
     }
+
+    fn get_object_readings(
+        &self,
+        max_range: f32,
+        fov_rad: f32,
+    ) -> Result<Vec<ObjectReading>, CommsError> {
+        SimRobotInterface::get_object_readings(self, max_range, fov_rad)
+    }
+
+    fn cmd_pickup(&self) -> Result<bool, CommsError> {
+        SimRobotInterface::cmd_pickup(self)
+    }
+
+    fn cmd_drop(&self) -> Result<bool, CommsError> {
+        SimRobotInterface::cmd_drop(self)
+    }
 }
@@ -21,10 +21,53 @@ impl Color {
     pub const BLUE: Color = Color::new(0, 0, 255, 255);
 
     pub fn from_str(color_str: &str) -> Self {
-        match color_str.to_lowercase().as_str() {
-            "red" => Self::RED,
-            "blue" => Self::BLUE,
-            _ => Self::BLACK,
+        Self::parse(color_str).unwrap_or(Self::BLACK)
+    }
+
+    /// Parses either a color name ("red") or a hex string ("#rrggbb" or
+    /// "#rrggbbaa"). Returns None if the string is neither.
+    pub fn parse(color_str: &str) -> Option<Self> {
+        let color_str = color_str.to_lowercase();
+        match color_str.as_str() {
+            "red" => return Some(Self::RED),
+            "blue" => return Some(Self::BLUE),
+            "black" => return Some(Self::BLACK),
+            _ => {}
+        }
+
+        let hex = color_str.strip_prefix('#')?;
+        if (hex.len() != 6 && hex.len() != 8) || !hex.is_ascii() {
+            return None;
         }
+
+        let byte_at = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        let a = if hex.len() == 8 { byte_at(6)? } else { 255 };
+
+        Some(Self::new(byte_at(0)?, byte_at(2)?, byte_at(4)?, a))
+    }
+
+    pub fn to_hex_string(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, self.a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Color::parse("Red"), Some(Color::RED));
+        assert_eq!(Color::parse("#0000ff"), Some(Color::BLUE));
+        assert_eq!(Color::parse("#01020304"), Some(Color::new(1, 2, 3, 4)));
+        assert_eq!(Color::parse("#0102"), None);
+        assert_eq!(Color::parse("#gg0000"), None);
+        assert_eq!(Color::parse("chartreuse"), None);
+    }
+
+    #[test]
+    fn test_hex_round_trip() {
+        let color = Color::new(10, 20, 30, 40);
+        assert_eq!(Color::parse(&color.to_hex_string()), Some(color));
     }
 }
@@ -1,409 +1,639 @@
+use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use super::color::*;
 use super::messages::*;
+use super::object_reading::*;
 use super::robot_position::*;
+use super::sensor_config::*;
 use super::vec3d::*;
 
 pub const CONSOLE_PORT: u16 = 50000;
 
-pub fn parse_message(msg_buf: &[u8]) -> Result<Box<dyn Message>, Box<dyn Error>> {
-    if msg_buf.len() < 1 {
-        return Err("msg buf doesnt' have type byte")?;
-    }
-
-    if msg_buf[0] == MessageType::Alive as u8 {
-        let msg = parse_alive_message(msg_buf)?;
-        return Ok(Box::new(msg));
-    }
-
-    if msg_buf[0] == MessageType::Start as u8 {
-        let msg = parse_start_message(msg_buf)?;
-        return Ok(Box::new(msg));
-    }
-
-    if msg_buf[0] == MessageType::RequestPosition as u8 {
-        let msg = parse_request_position_message(msg_buf)?;
-        return Ok(Box::new(msg));
-    }
-
-    if msg_buf[0] == MessageType::Position as u8 {
-        let msg = parse_position_message(msg_buf)?;
-        return Ok(Box::new(msg));
-    }
-
-    if msg_buf[0] == MessageType::Kill as u8 {
-        let msg = parse_kill_message(msg_buf)?;
-        return Ok(Box::new(msg));
-    }
-
-    if msg_buf[0] == MessageType::RobotDying as u8 {
-        let msg = parse_robot_dying_message(msg_buf)?;
-        return Ok(Box::new(msg));
-    }
-
-    if msg_buf[0] == MessageType::GetObstacles as u8 {
-        let msg = parse_get_obstacles_message(msg_buf)?;
-        return Ok(Box::new(msg));
-    }
-
-    if msg_buf[0] == MessageType::ObsReadings as u8 {
-        let msg = parse_obs_readings_message(msg_buf)?;
-        return Ok(Box::new(msg));
-    }
-
-    if msg_buf[0] == MessageType::Pause as u8 {
-        let msg = parse_pause_message(msg_buf)?;
-        return Ok(Box::new(msg));
-    }
-
-    if msg_buf[0] == MessageType::Move as u8 {
-        let msg = parse_move_message(msg_buf)?;
-        return Ok(Box::new(msg));
-    }
+/// The sender ID the console puts in its headers. Robots can't use it, since
+/// their port would be the console's.
+pub const CONSOLE_ID: u32 = 0;
+
+pub const PROTOCOL_MAGIC: &[u8; 2] = b"RS";
+/// Bumped whenever the layout of any message changes.
+pub const PROTOCOL_VERSION: u8 = 3;
+pub const HEADER_LEN: usize = 2 + 1 + 4 + 4 + 4 + 8 + 2 + 2;
+
+/// The most that's ever sent in one datagram. Messages longer than this go
+/// out in fragments, each with its own header.
+pub const MAX_DATAGRAM_LEN: usize = 1024;
+const MAX_FRAGMENT_BODY_LEN: usize = MAX_DATAGRAM_LEN - HEADER_LEN;
+// caps a message at about 1 MB, which is plenty for the densest sensor
+const MAX_FRAGMENTS: usize = 1024;
+
+#[derive(Debug)]
+pub enum CommsError {
+    /// A message buffer that isn't the size its type calls for, usually
+    /// because the datagram was cut short.
+    BadLength {
+        msg_label: &'static str,
+        len: usize,
+    },
+    BadType(u8),
+    /// Something that isn't a RoboSim message at all.
+    BadMagic,
+    /// A message from a console or robot built with a different protocol.
+    VersionMismatch {
+        version: u8,
+    },
+    /// A fragment that doesn't fit with the rest of its message.
+    BadFragment {
+        index: u16,
+        count: u16,
+    },
+    /// A message with more to it than fits in MAX_FRAGMENTS datagrams.
+    TooLong(usize),
+    UnknownRobot(u32),
+    /// A request that was sent, and re-sent, without ever being answered.
+    NoReply {
+        request: MessageType,
+        attempts: u32,
+    },
+    /// A robot said it was alive using an ID another robot already has.
+    DuplicateRobot(u32),
+    Socket(io::Error),
+}
 
-    if msg_buf[0] == MessageType::Spin as u8 {
-        let msg = parse_spin_message(msg_buf)?;
-        return Ok(Box::new(msg));
+impl fmt::Display for CommsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommsError::BadLength { msg_label, len } => {
+                write!(f, "{} msg has the wrong size: {} bytes", msg_label, len)
+            }
+            CommsError::BadType(type_byte) => write!(f, "bad msg type byte: {}", type_byte),
+            CommsError::BadMagic => write!(f, "msg doesn't start with the protocol magic"),
+            CommsError::VersionMismatch { version } => write!(
+                f,
+                "msg has protocol version {}, but this build speaks version {}",
+                version, PROTOCOL_VERSION
+            ),
+            CommsError::BadFragment { index, count } => {
+                write!(f, "bad msg fragment: {} of {}", index, count)
+            }
+            CommsError::TooLong(len) => write!(f, "msg is too long to send: {} bytes", len),
+            CommsError::UnknownRobot(id) => write!(f, "unknown robot ID: {}", id),
+            CommsError::NoReply { request, attempts } => write!(
+                f,
+                "no reply to {:?} msg after {} attempts",
+                request, attempts
+            ),
+            CommsError::DuplicateRobot(id) => write!(f, "ID {} is being re-used", id),
+            CommsError::Socket(err) => write!(f, "socket error: {}", err),
+        }
     }
-
-    Err(format!("bad msg type byte: {}", msg_buf[0]))?
 }
 
-const ALIVE_MSG_LEN: usize = 1 + 4 + (4 * 4) + 4 + (4 * 3);
-const START_MSG_LEN: usize = 1;
-const REQUEST_POSITION_MSG_LEN: usize = 1 + 4;
-const POSITION_MSG_LEN: usize = 1 + 4 + (4 * 4);
-const KILL_MSG_LEN: usize = 1;
-const ROBOT_DYING_MSG_LEN: usize = 1 + 4;
-const GET_OBSTACLES_MSG_LEN: usize = 1 + 4;
-const OBS_READING_SIZE: usize = 12;
-const PAUSE_MSG_LEN: usize = 1;
-const MOVE_MSG_LEN: usize = 1 + 4 + 4 + 4;
-const SPIN_MSG_LEN: usize = 1 + 4 + 4;
-
-fn to_arr_4(buf: &[u8], offset: usize) -> [u8; 4] {
-    [
-        buf[offset + 0],
-        buf[offset + 1],
-        buf[offset + 2],
-        buf[offset + 3],
-    ]
-}
+impl Error for CommsError {}
 
-fn arr4_into_vec(dst: &mut Vec<u8>, offset: usize, src: [u8; 4]) {
-    dst[offset + 0] = src[0];
-    dst[offset + 1] = src[1];
-    dst[offset + 2] = src[2];
-    dst[offset + 3] = src[3];
+impl From<io::Error> for CommsError {
+    fn from(err: io::Error) -> Self {
+        CommsError::Socket(err)
+    }
 }
 
-fn check_msg_buf_len(
-    msg_buf: &[u8],
-    expected: usize,
-    msg_label: &str,
-) -> Result<(), Box<dyn Error>> {
-    if msg_buf.len() != expected {
-        return Err(format!(
-            "{} msg wrong size. got {}, expected {} bytes",
-            msg_label,
-            msg_buf.len(),
-            expected
-        ))?;
-    }
-
-    Ok(())
+/// Comes in front of every message on the wire.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MsgHeader {
+    pub version: u8,
+    pub sender_id: u32,
+    // counts up with each message from a given sender, but is never 0
+    pub seq: u32,
+    // for replies, the seq of the request being answered
+    pub reply_to: Option<u32>,
+    // wall-clock time when the message was sent, in ms since the Unix epoch
+    pub timestamp_ms: u64,
+    // which piece of the message this is, for messages too long for one
+    // datagram; a whole message is fragment 0 of 1
+    pub frag_index: u16,
+    pub frag_count: u16,
 }
 
-fn check_msg_buf_expected_type(
-    msg_buf: &[u8],
-    expected: MessageType,
-    msg_label: &str,
-) -> Result<(), Box<dyn Error>> {
-    if msg_buf[0] != expected as u8 {
-        return Err(format!(
-            "{} msg buf has wrong start byte: {}",
-            msg_label, msg_buf[0]
-        ))?;
+impl MsgHeader {
+    pub fn new(sender_id: u32, seq: u32, reply_to: Option<u32>, timestamp_ms: u64) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            sender_id: sender_id,
+            seq: seq,
+            reply_to: reply_to,
+            timestamp_ms: timestamp_ms,
+            frag_index: 0,
+            frag_count: 1,
+        }
     }
-
-    Ok(())
-}
-
-fn parse_alive_message(msg_buf: &[u8]) -> Result<AliveMsg, Box<dyn Error>> {
-    check_msg_buf_len(msg_buf, ALIVE_MSG_LEN, "ALIVE")?;
-    check_msg_buf_expected_type(msg_buf, MessageType::Alive, "ALIVE")?;
-
-    let id = u32::from_be_bytes(to_arr_4(msg_buf, 1));
-
-    let loc = Vec3d::new(
-        f32::from_be_bytes(to_arr_4(msg_buf, 5)),
-        f32::from_be_bytes(to_arr_4(msg_buf, 9)),
-        f32::from_be_bytes(to_arr_4(msg_buf, 13)),
-    );
-    let heading_rad = f32::from_be_bytes(to_arr_4(msg_buf, 17));
-
-    let color = Color::new(msg_buf[21], msg_buf[22], msg_buf[23], msg_buf[24]);
-
-    let max_vel = f32::from_be_bytes(to_arr_4(msg_buf, 25));
-    let max_angular_vel = f32::from_be_bytes(to_arr_4(msg_buf, 29));
-    let radius = f32::from_be_bytes(to_arr_4(msg_buf, 33));
-
-    Ok(AliveMsg::new(
-        id,
-        RobotPosition::new(loc, heading_rad),
-        color,
-        max_vel,
-        max_angular_vel,
-        radius,
-    ))
 }
 
-pub fn pack_alive_message(msg: AliveMsg) -> Vec<u8> {
-    let mut buf = vec![0; ALIVE_MSG_LEN];
-
-    buf[0] = msg.msg_type as u8;
-
-    arr4_into_vec(&mut buf, 1, u32::to_be_bytes(msg.id));
-
-    arr4_into_vec(&mut buf, 5, f32::to_be_bytes(msg.pos.location.x));
-    arr4_into_vec(&mut buf, 9, f32::to_be_bytes(msg.pos.location.y));
-    arr4_into_vec(&mut buf, 13, f32::to_be_bytes(msg.pos.location.z));
-    arr4_into_vec(&mut buf, 17, f32::to_be_bytes(msg.pos.heading_rad));
-
-    buf[21] = msg.color.r;
-    buf[22] = msg.color.g;
-    buf[23] = msg.color.b;
-    buf[24] = msg.color.a;
-
-    arr4_into_vec(&mut buf, 25, f32::to_be_bytes(msg.max_vel));
-    arr4_into_vec(&mut buf, 29, f32::to_be_bytes(msg.max_angular_vel));
-    arr4_into_vec(&mut buf, 33, f32::to_be_bytes(msg.radius));
-
-    buf
+/// The sequence number to use after seq. It skips 0, which is how the header
+/// says a message isn't a reply.
+pub fn next_seq(seq: u32) -> u32 {
+    seq.wrapping_add(1).max(1)
 }
 
-fn parse_start_message(msg_buf: &[u8]) -> Result<StartMsg, Box<dyn Error>> {
-    check_msg_buf_len(msg_buf, START_MSG_LEN, "START")?;
-    check_msg_buf_expected_type(msg_buf, MessageType::Start, "START")?;
-
-    Ok(StartMsg::new())
+pub fn timestamp_now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
 }
 
-pub fn pack_start_message(msg: StartMsg) -> Vec<u8> {
-    let mut buf = vec![0; START_MSG_LEN];
-
-    buf[0] = msg.msg_type as u8;
-
+/// Puts a header in front of a message packed with WireMessage::pack(). The
+/// result can be any length, so it's for keeping, not sending; see
+/// fragment_message().
+pub fn frame_message(header: MsgHeader, msg_buf: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_LEN + msg_buf.len());
+
+    buf.extend_from_slice(PROTOCOL_MAGIC);
+    header.version.encode(&mut buf);
+    header.sender_id.encode(&mut buf);
+    header.seq.encode(&mut buf);
+    header.reply_to.unwrap_or(0).encode(&mut buf);
+    header.timestamp_ms.encode(&mut buf);
+    header.frag_index.encode(&mut buf);
+    header.frag_count.encode(&mut buf);
+
+    buf.extend_from_slice(msg_buf);
     buf
 }
 
-fn parse_request_position_message(msg_buf: &[u8]) -> Result<RequestPositionMsg, Box<dyn Error>> {
-    check_msg_buf_len(msg_buf, REQUEST_POSITION_MSG_LEN, "REQUEST_POSITION")?;
-    check_msg_buf_expected_type(msg_buf, MessageType::RequestPosition, "REQUEST_POSITION")?;
-
-    let id = u32::from_be_bytes(to_arr_4(msg_buf, 1));
-
-    Ok(RequestPositionMsg::new(id))
-}
-
-pub fn pack_request_position_message(msg: RequestPositionMsg) -> Vec<u8> {
-    let mut buf = vec![0; REQUEST_POSITION_MSG_LEN];
-
-    buf[0] = msg.msg_type as u8;
+/// Splits a message into datagrams of at most MAX_DATAGRAM_LEN, each framed
+/// with the message's header. Most messages fit in one.
+pub fn fragment_message(header: MsgHeader, msg_buf: &[u8]) -> Result<Vec<Vec<u8>>, CommsError> {
+    if msg_buf.len() <= MAX_FRAGMENT_BODY_LEN {
+        return Ok(vec![frame_message(header, msg_buf)]);
+    }
 
-    arr4_into_vec(&mut buf, 1, u32::to_be_bytes(msg.id));
+    let chunks: Vec<&[u8]> = msg_buf.chunks(MAX_FRAGMENT_BODY_LEN).collect();
+    if chunks.len() > MAX_FRAGMENTS {
+        return Err(CommsError::TooLong(msg_buf.len()));
+    }
 
-    buf
+    let frag_count = chunks.len() as u16;
+    Ok(chunks
+        .iter()
+        .enumerate()
+        .map(|(frag_index, chunk)| {
+            let frag_header = MsgHeader {
+                frag_index: frag_index as u16,
+                frag_count: frag_count,
+                ..header
+            };
+            frame_message(frag_header, chunk)
+        })
+        .collect())
 }
 
-fn parse_position_message(msg_buf: &[u8]) -> Result<PositionMsg, Box<dyn Error>> {
-    check_msg_buf_len(msg_buf, POSITION_MSG_LEN, "POSITION")?;
-    check_msg_buf_expected_type(msg_buf, MessageType::Position, "POSITION")?;
-
-    let id = u32::from_be_bytes(to_arr_4(msg_buf, 1));
-
-    let loc = Vec3d::new(
-        f32::from_be_bytes(to_arr_4(msg_buf, 5)),
-        f32::from_be_bytes(to_arr_4(msg_buf, 9)),
-        f32::from_be_bytes(to_arr_4(msg_buf, 13)),
-    );
-    let heading_rad = f32::from_be_bytes(to_arr_4(msg_buf, 17));
-
-    Ok(PositionMsg::new(id, RobotPosition::new(loc, heading_rad)))
-}
+fn parse_header(buf: &[u8]) -> Result<MsgHeader, CommsError> {
+    if !buf.starts_with(PROTOCOL_MAGIC) {
+        return Err(CommsError::BadMagic);
+    }
 
-pub fn pack_position_message(msg: PositionMsg) -> Vec<u8> {
-    let mut buf = vec![0; POSITION_MSG_LEN];
+    // the header has changed size between versions, so a message from
+    // another build is caught before its length can be misjudged
+    if let Some(&version) = buf.get(PROTOCOL_MAGIC.len()) {
+        if version != PROTOCOL_VERSION {
+            return Err(CommsError::VersionMismatch { version: version });
+        }
+    }
 
-    buf[0] = msg.msg_type as u8;
+    if buf.len() < HEADER_LEN {
+        return Err(CommsError::BadLength {
+            msg_label: "HEADER",
+            len: buf.len(),
+        });
+    }
 
-    arr4_into_vec(&mut buf, 1, u32::to_be_bytes(msg.id));
-    arr4_into_vec(&mut buf, 5, f32::to_be_bytes(msg.pos.location.x));
-    arr4_into_vec(&mut buf, 9, f32::to_be_bytes(msg.pos.location.y));
-    arr4_into_vec(&mut buf, 13, f32::to_be_bytes(msg.pos.location.z));
-    arr4_into_vec(&mut buf, 17, f32::to_be_bytes(msg.pos.heading_rad));
+    let mut reader = WireReader::new(&buf[PROTOCOL_MAGIC.len() + 1..HEADER_LEN], "HEADER");
+    let sender_id = WireField::decode(&mut reader)?;
+    let seq = WireField::decode(&mut reader)?;
+    let reply_to = match u32::decode(&mut reader)? {
+        0 => None,
+        request_seq => Some(request_seq),
+    };
+    let timestamp_ms = WireField::decode(&mut reader)?;
+    let frag_index = WireField::decode(&mut reader)?;
+    let frag_count = WireField::decode(&mut reader)?;
+    if frag_index >= frag_count {
+        return Err(CommsError::BadFragment {
+            index: frag_index,
+            count: frag_count,
+        });
+    }
 
-    buf
+    Ok(MsgHeader {
+        frag_index: frag_index,
+        frag_count: frag_count,
+        ..MsgHeader::new(sender_id, seq, reply_to, timestamp_ms)
+    })
 }
 
-fn parse_kill_message(msg_buf: &[u8]) -> Result<KillMsg, Box<dyn Error>> {
-    check_msg_buf_len(msg_buf, KILL_MSG_LEN, "KILL")?;
-    check_msg_buf_expected_type(msg_buf, MessageType::Kill, "KILL")?;
+/// Parses a whole message, header and all, as framed by frame_message() or
+/// put back together by a Reassembler.
+pub fn parse_message(buf: &[u8]) -> Result<(MsgHeader, Box<dyn Message>), CommsError> {
+    let header = parse_header(buf)?;
+    if header.frag_count != 1 {
+        return Err(CommsError::BadFragment {
+            index: header.frag_index,
+            count: header.frag_count,
+        });
+    }
 
-    Ok(KillMsg::new())
+    let msg = parse_message_body(&buf[HEADER_LEN..])?;
+    Ok((header, msg))
 }
 
-pub fn pack_kill_message(msg: KillMsg) -> Vec<u8> {
-    let mut buf = vec![0; KILL_MSG_LEN];
-
-    buf[0] = msg.msg_type as u8;
-
-    buf
+// the fragments of one message that have come in so far
+struct PartialMsg {
+    header: MsgHeader,
+    frags: Vec<Option<Vec<u8>>>,
+    // when this message was started, in datagrams seen by the reassembler
+    started: u64,
 }
 
-fn parse_robot_dying_message(msg_buf: &[u8]) -> Result<RobotDyingMsg, Box<dyn Error>> {
-    check_msg_buf_len(msg_buf, ROBOT_DYING_MSG_LEN, "ROBOT_DYING")?;
-    check_msg_buf_expected_type(msg_buf, MessageType::RobotDying, "ROBOT_DYING")?;
-
-    let id = u32::from_be_bytes(to_arr_4(msg_buf, 1));
-
-    Ok(RobotDyingMsg::new(id))
+/// Puts fragmented messages back together as their datagrams come in, in
+/// whatever order. A message missing a fragment is eventually given up on;
+/// requests are re-sent anyway if their replies don't show up.
+pub struct Reassembler {
+    // keyed by sender ID and seq
+    partial_msgs: HashMap<(u32, u32), PartialMsg>,
+    datagrams_seen: u64,
 }
 
-pub fn pack_robot_dying_message(msg: RobotDyingMsg) -> Vec<u8> {
-    let mut buf = vec![0; ROBOT_DYING_MSG_LEN];
+impl Reassembler {
+    // any more than this, and the one started longest ago is dropped
+    const MAX_PARTIAL_MSGS: usize = 16;
 
-    buf[0] = msg.msg_type as u8;
+    pub fn new() -> Self {
+        Self {
+            partial_msgs: HashMap::new(),
+            datagrams_seen: 0,
+        }
+    }
 
-    arr4_into_vec(&mut buf, 1, u32::to_be_bytes(msg.id));
+    /// Takes in one datagram, and returns the whole message (framed as if it
+    /// had never been fragmented) once all of it has arrived.
+    pub fn add(&mut self, datagram: &[u8]) -> Result<Option<Vec<u8>>, CommsError> {
+        let header = parse_header(datagram)?;
+        if header.frag_count == 1 {
+            return Ok(Some(datagram.to_vec()));
+        }
+
+        // more than anything could have sent
+        if header.frag_count as usize > MAX_FRAGMENTS {
+            return Err(CommsError::BadFragment {
+                index: header.frag_index,
+                count: header.frag_count,
+            });
+        }
+
+        self.datagrams_seen += 1;
+        let key = (header.sender_id, header.seq);
+        if !self.partial_msgs.contains_key(&key) {
+            self.make_room();
+        }
+        let started = self.datagrams_seen;
+        let partial_msg = self.partial_msgs.entry(key).or_insert_with(|| PartialMsg {
+            header: header,
+            frags: vec![None; header.frag_count as usize],
+            started: started,
+        });
+        if partial_msg.header.frag_count != header.frag_count {
+            return Err(CommsError::BadFragment {
+                index: header.frag_index,
+                count: header.frag_count,
+            });
+        }
+
+        // a repeat of a fragment that's already in is ignored
+        partial_msg.frags[header.frag_index as usize] = Some(datagram[HEADER_LEN..].to_vec());
+        if partial_msg.frags.iter().any(|frag| frag.is_none()) {
+            return Ok(None);
+        }
+
+        let partial_msg = self.partial_msgs.remove(&key).unwrap();
+        let msg_buf: Vec<u8> = partial_msg.frags.into_iter().flatten().flatten().collect();
+        let whole_header = MsgHeader {
+            frag_index: 0,
+            frag_count: 1,
+            ..partial_msg.header
+        };
+        Ok(Some(frame_message(whole_header, &msg_buf)))
+    }
 
-    buf
+    fn make_room(&mut self) {
+        if self.partial_msgs.len() < Reassembler::MAX_PARTIAL_MSGS {
+            return;
+        }
+
+        let oldest = self
+            .partial_msgs
+            .iter()
+            .min_by_key(|(_, partial_msg)| partial_msg.started)
+            .map(|(key, _)| *key);
+        if let Some(key) = oldest {
+            println!(
+                "Error: gave up on msg {} from {}, which is missing fragments",
+                key.1, key.0
+            );
+            self.partial_msgs.remove(&key);
+        }
+    }
 }
 
-fn parse_get_obstacles_message(msg_buf: &[u8]) -> Result<GetObstaclesMsg, Box<dyn Error>> {
-    check_msg_buf_len(msg_buf, GET_OBSTACLES_MSG_LEN, "GET_OBSTACLES")?;
-    check_msg_buf_expected_type(msg_buf, MessageType::GetObstacles, "GET_OBSTACLES")?;
-
-    let id = u32::from_be_bytes(to_arr_4(msg_buf, 1));
+/// Packing and unpacking for a message. On the wire, every message is its
+/// type byte followed by each of its fields in order, big-endian.
+pub trait WireMessage: Message + Sized {
+    const MSG_TYPE: MessageType;
 
-    Ok(GetObstaclesMsg::new(id))
+    fn pack(&self) -> Vec<u8>;
+    fn unpack(msg_buf: &[u8]) -> Result<Self, CommsError>;
 }
 
-pub fn pack_get_obstacles_message(msg: GetObstaclesMsg) -> Vec<u8> {
-    let mut buf = vec![0; GET_OBSTACLES_MSG_LEN];
-
-    buf[0] = msg.msg_type as u8;
-
-    arr4_into_vec(&mut buf, 1, u32::to_be_bytes(msg.id));
-
-    buf
+/// Walks through a message buffer a field at a time. Recordings use it too,
+/// so they're laid out the same way messages are.
+pub struct WireReader<'a> {
+    buf: &'a [u8],
+    offset: usize,
+    msg_label: &'static str,
 }
 
-fn parse_obs_readings_message(msg_buf: &[u8]) -> Result<ObsReadingsMsg, Box<dyn Error>> {
-    if msg_buf.len() < 1 || ((msg_buf.len() - 1) % OBS_READING_SIZE) != 0 {
-        return Err(format!(
-            "{} msg wrong size. got {}, expected (1 + 8n) bytes",
-            "OBS_READINGS",
-            msg_buf.len()
-        ))?;
+impl<'a> WireReader<'a> {
+    pub fn new(buf: &'a [u8], msg_label: &'static str) -> Self {
+        Self {
+            buf: buf,
+            offset: 0,
+            msg_label: msg_label,
+        }
     }
 
-    check_msg_buf_expected_type(msg_buf, MessageType::ObsReadings, "OBS_READINGS")?;
-
-    let num_readings = (msg_buf.len() - 1) / OBS_READING_SIZE;
-    let mut readings = Vec::with_capacity(num_readings);
-
-    let mut offset = 1;
-    while offset < msg_buf.len() {
-        let x = f32::from_be_bytes(to_arr_4(msg_buf, offset));
-        offset += 4;
-        let y = f32::from_be_bytes(to_arr_4(msg_buf, offset));
-        offset += 4;
-        let z = f32::from_be_bytes(to_arr_4(msg_buf, offset));
-        offset += 4;
-
-        readings.push(Vec3d::<f32>::new(x, y, z));
+    fn bad_length(&self) -> CommsError {
+        CommsError::BadLength {
+            msg_label: self.msg_label,
+            len: self.buf.len(),
+        }
     }
 
-    Ok(ObsReadingsMsg::new(readings))
-}
+    pub fn take<const N: usize>(&mut self) -> Result<[u8; N], CommsError> {
+        let mut arr = [0; N];
+        arr.copy_from_slice(self.take_slice(N)?);
+        Ok(arr)
+    }
 
-pub fn pack_obs_readings_message(msg: ObsReadingsMsg) -> Vec<u8> {
-    let mut buf = vec![0; 1 + msg.readings.len() * OBS_READING_SIZE];
+    pub fn take_slice(&mut self, len: usize) -> Result<&'a [u8], CommsError> {
+        let end = self.offset + len;
+        if end > self.buf.len() {
+            return Err(self.bad_length());
+        }
 
-    buf[0] = msg.msg_type as u8;
+        let slice = &self.buf[self.offset..end];
+        self.offset = end;
+        Ok(slice)
+    }
 
-    let mut offset = 1;
-    for reading in msg.readings {
-        arr4_into_vec(&mut buf, offset, f32::to_be_bytes(reading.x));
-        offset += 4;
-        arr4_into_vec(&mut buf, offset, f32::to_be_bytes(reading.y));
-        offset += 4;
-        arr4_into_vec(&mut buf, offset, f32::to_be_bytes(reading.z));
-        offset += 4;
+    /// How far into the buffer the next field starts.
+    pub fn offset(&self) -> usize {
+        self.offset
     }
 
-    buf
-}
+    pub fn is_empty(&self) -> bool {
+        self.offset >= self.buf.len()
+    }
 
-fn parse_pause_message(msg_buf: &[u8]) -> Result<PauseMsg, Box<dyn Error>> {
-    check_msg_buf_len(msg_buf, PAUSE_MSG_LEN, "PAUSE")?;
-    check_msg_buf_expected_type(msg_buf, MessageType::Pause, "PAUSE")?;
+    // anything left over means the message is longer than its type allows
+    pub fn finish(&self) -> Result<(), CommsError> {
+        if !self.is_empty() {
+            return Err(self.bad_length());
+        }
 
-    Ok(PauseMsg::new())
+        Ok(())
+    }
 }
 
-pub fn pack_pause_message(msg: PauseMsg) -> Vec<u8> {
-    let mut buf = vec![0; PAUSE_MSG_LEN];
+/// Anything that can be a field in a message.
+pub trait WireField: Sized {
+    fn encode(&self, buf: &mut Vec<u8>);
+    fn decode(reader: &mut WireReader) -> Result<Self, CommsError>;
 
-    buf[0] = msg.msg_type as u8;
-
-    buf
+    // some value to fill the field with when testing every message
+    #[cfg(test)]
+    fn example() -> Self;
 }
 
-fn parse_move_message(msg_buf: &[u8]) -> Result<MoveMsg, Box<dyn Error>> {
-    check_msg_buf_len(msg_buf, MOVE_MSG_LEN, "MOVE")?;
-    check_msg_buf_expected_type(msg_buf, MessageType::Move, "MOVE")?;
-
-    let id = u32::from_be_bytes(to_arr_4(msg_buf, 1));
-    let x = f32::from_be_bytes(to_arr_4(msg_buf, 5));
-    let y = f32::from_be_bytes(to_arr_4(msg_buf, 9));
-
-    Ok(MoveMsg::new(id, x, y))
+macro_rules! wire_numbers {
+    ($($num:ty),*) => {
+        $(
+            impl WireField for $num {
+                fn encode(&self, buf: &mut Vec<u8>) {
+                    buf.extend_from_slice(&self.to_be_bytes());
+                }
+
+                fn decode(reader: &mut WireReader) -> Result<Self, CommsError> {
+                    Ok(<$num>::from_be_bytes(reader.take()?))
+                }
+
+                #[cfg(test)]
+                fn example() -> Self {
+                    7 as $num
+                }
+            }
+        )*
+    };
 }
 
-pub fn pack_move_message(msg: MoveMsg) -> Vec<u8> {
-    let mut buf = vec![0; MOVE_MSG_LEN];
+wire_numbers!(u8, u16, u32, u64, f32, f64);
 
-    buf[0] = msg.msg_type as u8;
+impl WireField for bool {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(*self as u8);
+    }
 
-    arr4_into_vec(&mut buf, 1, u32::to_be_bytes(msg.id));
-    arr4_into_vec(&mut buf, 5, f32::to_be_bytes(msg.x));
-    arr4_into_vec(&mut buf, 9, f32::to_be_bytes(msg.y));
+    fn decode(reader: &mut WireReader) -> Result<Self, CommsError> {
+        Ok(u8::decode(reader)? != 0)
+    }
 
-    buf
+    #[cfg(test)]
+    fn example() -> Self {
+        true
+    }
 }
 
-fn parse_spin_message(msg_buf: &[u8]) -> Result<SpinMsg, Box<dyn Error>> {
-    check_msg_buf_len(msg_buf, SPIN_MSG_LEN, "SPIN")?;
-    check_msg_buf_expected_type(msg_buf, MessageType::Spin, "SPIN")?;
+// a list of readings takes up the rest of its message, so it has to be the
+// last field
+impl<T: WireField> WireField for Vec<T> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        for item in self {
+            item.encode(buf);
+        }
+    }
 
-    let id = u32::from_be_bytes(to_arr_4(msg_buf, 1));
-    let theta = f32::from_be_bytes(to_arr_4(msg_buf, 5));
+    fn decode(reader: &mut WireReader) -> Result<Self, CommsError> {
+        let mut items = vec![];
+        while !reader.is_empty() {
+            items.push(T::decode(reader)?);
+        }
+        Ok(items)
+    }
 
-    Ok(SpinMsg::new(id, theta))
+    #[cfg(test)]
+    fn example() -> Self {
+        vec![T::example(), T::example()]
+    }
 }
 
-pub fn pack_spin_message(msg: SpinMsg) -> Vec<u8> {
-    let mut buf = vec![0; SPIN_MSG_LEN];
+// structs that go over the wire as their fields, in order
+macro_rules! wire_structs {
+    ($($name:ty { $($field:ident),* }),* $(,)?) => {
+        $(
+            impl WireField for $name {
+                fn encode(&self, buf: &mut Vec<u8>) {
+                    $(self.$field.encode(buf);)*
+                }
+
+                fn decode(reader: &mut WireReader) -> Result<Self, CommsError> {
+                    Ok(Self {
+                        $($field: WireField::decode(reader)?,)*
+                    })
+                }
+
+                #[cfg(test)]
+                fn example() -> Self {
+                    Self {
+                        $($field: WireField::example(),)*
+                    }
+                }
+            }
+        )*
+    };
+}
 
-    buf[0] = msg.msg_type as u8;
+wire_structs! {
+    Color { r, g, b, a },
+    Vec3d<f32> { x, y, z },
+    RobotPosition { location, heading_rad },
+    SensorConfig {
+        num_rays,
+        fov_rad,
+        max_range,
+        angle_offset_rad,
+        range_noise_std_dev,
+        dropout_prob
+    },
+    ObjectReading { pos, radius, color },
+}
 
-    arr4_into_vec(&mut buf, 1, u32::to_be_bytes(msg.id));
-    arr4_into_vec(&mut buf, 5, f32::to_be_bytes(msg.theta));
+// Every message, with the fields that go over the wire. This generates each
+// message's WireMessage impl and the type byte dispatch in
+// parse_message_body(), so adding a message only means adding it here.
+macro_rules! wire_messages {
+    ($($msg_type:ident => $msg:ident { $($field:ident),* }),* $(,)?) => {
+        $(
+            impl WireMessage for $msg {
+                const MSG_TYPE: MessageType = MessageType::$msg_type;
+
+                fn pack(&self) -> Vec<u8> {
+                    // messages with no fields never touch these
+                    #[allow(unused_mut)]
+                    let mut buf = vec![Self::MSG_TYPE as u8];
+                    $(self.$field.encode(&mut buf);)*
+                    buf
+                }
+
+                fn unpack(msg_buf: &[u8]) -> Result<Self, CommsError> {
+                    match msg_buf.first() {
+                        Some(type_byte) if *type_byte == Self::MSG_TYPE as u8 => {}
+                        Some(type_byte) => return Err(CommsError::BadType(*type_byte)),
+                        None => {
+                            return Err(CommsError::BadLength {
+                                msg_label: stringify!($msg_type),
+                                len: 0,
+                            })
+                        }
+                    }
+
+                    #[allow(unused_mut)]
+                    let mut reader = WireReader::new(&msg_buf[1..], stringify!($msg_type));
+                    let msg = Self {
+                        msg_type: Self::MSG_TYPE,
+                        $($field: WireField::decode(&mut reader)?,)*
+                    };
+                    reader.finish()?;
+
+                    Ok(msg)
+                }
+            }
+        )*
+
+        fn parse_message_body(msg_buf: &[u8]) -> Result<Box<dyn Message>, CommsError> {
+            let type_byte = match msg_buf.first() {
+                Some(type_byte) => *type_byte,
+                None => {
+                    return Err(CommsError::BadLength {
+                        msg_label: "EMPTY",
+                        len: 0,
+                    })
+                }
+            };
+
+            $(
+                if type_byte == MessageType::$msg_type as u8 {
+                    return Ok(Box::new($msg::unpack(msg_buf)?));
+                }
+            )*
+
+            Err(CommsError::BadType(type_byte))
+        }
+
+        // packs an example of every message and checks it comes back the same
+        #[cfg(test)]
+        fn check_every_message_round_trips() {
+            $(
+                let msg = $msg {
+                    msg_type: MessageType::$msg_type,
+                    $($field: WireField::example(),)*
+                };
+                let buf = msg.pack();
+
+                let parsed = parse_message_body(&buf).unwrap();
+                assert_eq!(parsed.get_type(), MessageType::$msg_type);
+                assert_eq!(downcast::<$msg>(&parsed, "round_trip"), &msg);
+
+                // and a byte short or a byte over is turned away
+                assert!(parse_message_body(&buf[..buf.len() - 1]).is_err());
+                let mut long_buf = buf.clone();
+                long_buf.push(0);
+                assert!(parse_message_body(&long_buf).is_err());
+            )*
+        }
+    };
+}
 
-    buf
+wire_messages! {
+    Alive => AliveMsg { id, pos, color, max_vel, max_angular_vel, radius },
+    Start => StartMsg { lockstep },
+    RequestPosition => RequestPositionMsg { id },
+    Position => PositionMsg { id, pos, sim_time },
+    Kill => KillMsg {},
+    RobotDying => RobotDyingMsg { id },
+    GetObstacles => GetObstaclesMsg { id },
+    ObsReadings => ObsReadingsMsg { readings },
+    Pause => PauseMsg {},
+    Move => MoveMsg { id, x, y },
+    Spin => SpinMsg { id, theta },
+    SensorConfig => SensorConfigMsg { id, config },
+    GetObjects => GetObjectsMsg { id, max_range, fov_rad },
+    ObjectReadings => ObjectReadingsMsg { readings },
+    Pickup => PickupMsg { id },
+    Drop => DropMsg { id },
+    CarryStatus => CarryStatusMsg { id, carrying },
+    GoalEvent => GoalEventMsg { id, goal_id, entered, elapsed_s },
+    Tick => TickMsg { tick, sim_time },
+    TickDone => TickDoneMsg { id, tick },
 }
 
 #[cfg(test)]
@@ -421,18 +651,18 @@ mod tests {
             20.0,
         );
 
-        let buf = pack_alive_message(msg);
-        let msg2 = parse_alive_message(buf.as_slice()).unwrap();
+        let buf = msg.pack();
+        let msg2 = AliveMsg::unpack(buf.as_slice()).unwrap();
 
         assert_eq!(msg, msg2);
     }
 
     #[test]
     fn test_start_message() {
-        let msg = StartMsg::new();
+        let msg = StartMsg::new(true);
 
-        let buf = pack_start_message(msg);
-        let msg2 = parse_start_message(buf.as_slice()).unwrap();
+        let buf = msg.pack();
+        let msg2 = StartMsg::unpack(buf.as_slice()).unwrap();
 
         assert_eq!(msg, msg2);
     }
@@ -441,18 +671,19 @@ mod tests {
     fn test_request_position_message() {
         let msg = RequestPositionMsg::new(5);
 
-        let buf = pack_request_position_message(msg);
-        let msg2 = parse_request_position_message(buf.as_slice()).unwrap();
+        let buf = msg.pack();
+        let msg2 = RequestPositionMsg::unpack(buf.as_slice()).unwrap();
 
         assert_eq!(msg, msg2);
     }
 
     #[test]
     fn test_position_message() {
-        let msg = PositionMsg::new(RobotPosition::new(Vec3d::new(1.5, 2.5, 3.5), 0.5));
+        let pos = RobotPosition::new(Vec3d::new(1.5, 2.5, 3.5), 0.5);
+        let msg = PositionMsg::new(5, pos, 1234.56789);
 
-        let buf = pack_position_message(msg);
-        let msg2 = parse_position_message(buf.as_slice()).unwrap();
+        let buf = msg.pack();
+        let msg2 = PositionMsg::unpack(buf.as_slice()).unwrap();
 
         assert_eq!(msg, msg2);
     }
@@ -461,8 +692,8 @@ mod tests {
     fn test_kill_message() {
         let msg = KillMsg::new();
 
-        let buf = pack_kill_message(msg);
-        let msg2 = parse_kill_message(buf.as_slice()).unwrap();
+        let buf = msg.pack();
+        let msg2 = KillMsg::unpack(buf.as_slice()).unwrap();
 
         assert_eq!(msg, msg2);
     }
@@ -471,8 +702,8 @@ mod tests {
     fn test_robot_dying_message() {
         let msg = RobotDyingMsg::new(5);
 
-        let buf = pack_robot_dying_message(msg);
-        let msg2 = parse_robot_dying_message(buf.as_slice()).unwrap();
+        let buf = msg.pack();
+        let msg2 = RobotDyingMsg::unpack(buf.as_slice()).unwrap();
 
         assert_eq!(msg, msg2);
     }
@@ -481,18 +712,22 @@ mod tests {
     fn test_get_obstacles_message() {
         let msg = GetObstaclesMsg::new(5);
 
-        let buf = pack_get_obstacles_message(msg);
-        let msg2 = parse_get_obstacles_message(buf.as_slice()).unwrap();
+        let buf = msg.pack();
+        let msg2 = GetObstaclesMsg::unpack(buf.as_slice()).unwrap();
 
         assert_eq!(msg, msg2);
     }
 
     #[test]
     fn test_obs_readings_message() {
-        let msg = ObsReadingsMsg::new(vec![(1.0, 2.0), (3.0, 4.0), (-1.0, -2.5)]);
+        let msg = ObsReadingsMsg::new(vec![
+            Vec3d::new(1.0, 2.0, 0.0),
+            Vec3d::new(3.0, 4.0, 0.0),
+            Vec3d::new(-1.0, -2.5, 0.0),
+        ]);
 
-        let buf = pack_obs_readings_message(msg.clone());
-        let msg2 = parse_obs_readings_message(buf.as_slice()).unwrap();
+        let buf = msg.pack();
+        let msg2 = ObsReadingsMsg::unpack(buf.as_slice()).unwrap();
 
         assert_eq!(msg, msg2);
     }
@@ -501,8 +736,8 @@ mod tests {
     fn test_pause_message() {
         let msg = PauseMsg::new();
 
-        let buf = pack_pause_message(msg);
-        let msg2 = parse_pause_message(buf.as_slice()).unwrap();
+        let buf = msg.pack();
+        let msg2 = PauseMsg::unpack(buf.as_slice()).unwrap();
 
         assert_eq!(msg, msg2);
     }
@@ -511,8 +746,8 @@ mod tests {
     fn test_move_message() {
         let msg = MoveMsg::new(5, 1.5, 2.5);
 
-        let buf = pack_move_message(msg);
-        let msg2 = parse_move_message(buf.as_slice()).unwrap();
+        let buf = msg.pack();
+        let msg2 = MoveMsg::unpack(buf.as_slice()).unwrap();
 
         assert_eq!(msg, msg2);
     }
@@ -521,9 +756,310 @@ mod tests {
     fn test_spin_message() {
         let msg = SpinMsg::new(5, 1.5);
 
-        let buf = pack_spin_message(msg);
-        let msg2 = parse_spin_message(buf.as_slice()).unwrap();
+        let buf = msg.pack();
+        let msg2 = SpinMsg::unpack(buf.as_slice()).unwrap();
+
+        assert_eq!(msg, msg2);
+    }
+
+    #[test]
+    fn test_sensor_config_message() {
+        let msg = SensorConfigMsg::new(5, SensorConfig::new(32, 3.0, 10.0, 0.5, 0.1, 0.05));
+
+        let buf = msg.pack();
+        let msg2 = SensorConfigMsg::unpack(buf.as_slice()).unwrap();
+
+        assert_eq!(msg, msg2);
+    }
+
+    #[test]
+    fn test_get_objects_message() {
+        let msg = GetObjectsMsg::new(5, 10.0, 1.5);
+
+        let buf = msg.pack();
+        let msg2 = GetObjectsMsg::unpack(buf.as_slice()).unwrap();
+
+        assert_eq!(msg, msg2);
+    }
+
+    #[test]
+    fn test_object_readings_message() {
+        let msg = ObjectReadingsMsg::new(vec![
+            ObjectReading::new(Vec3d::new(1.0, 2.0, 0.0), 0.5, Color::RED),
+            ObjectReading::new(Vec3d::new(-3.0, 4.5, 0.0), 1.0, Color::new(1, 2, 3, 4)),
+        ]);
+
+        let buf = msg.pack();
+        let msg2 = ObjectReadingsMsg::unpack(buf.as_slice()).unwrap();
+
+        assert_eq!(msg, msg2);
+    }
+
+    #[test]
+    fn test_pickup_message() {
+        let msg = PickupMsg::new(5);
+
+        let buf = msg.pack();
+        let msg2 = PickupMsg::unpack(buf.as_slice()).unwrap();
+
+        assert_eq!(msg, msg2);
+    }
+
+    #[test]
+    fn test_drop_message() {
+        let msg = DropMsg::new(5);
+
+        let buf = msg.pack();
+        let msg2 = DropMsg::unpack(buf.as_slice()).unwrap();
+
+        assert_eq!(msg, msg2);
+    }
+
+    #[test]
+    fn test_carry_status_message() {
+        let msg = CarryStatusMsg::new(5, true);
+
+        let buf = msg.pack();
+        let msg2 = CarryStatusMsg::unpack(buf.as_slice()).unwrap();
+
+        assert_eq!(msg, msg2);
+    }
+
+    #[test]
+    fn test_goal_event_message() {
+        let msg = GoalEventMsg::new(5, 2, true, 12.5);
+
+        let buf = msg.pack();
+        let msg2 = GoalEventMsg::unpack(buf.as_slice()).unwrap();
+
+        assert_eq!(msg, msg2);
+    }
+
+    #[test]
+    fn test_tick_message() {
+        let msg = TickMsg::new(1 << 40, 12.5);
+
+        let buf = msg.pack();
+        let msg2 = TickMsg::unpack(buf.as_slice()).unwrap();
 
         assert_eq!(msg, msg2);
     }
+
+    #[test]
+    fn test_tick_done_message() {
+        let msg = TickDoneMsg::new(5, 1 << 40);
+
+        let buf = msg.pack();
+        let msg2 = TickDoneMsg::unpack(buf.as_slice()).unwrap();
+
+        assert_eq!(msg, msg2);
+    }
+
+    #[test]
+    fn test_every_message() {
+        check_every_message_round_trips();
+    }
+
+    #[test]
+    fn test_framing() {
+        let header = MsgHeader::new(5, 42, Some(41), 1_600_000_000_000);
+        let buf = frame_message(header, &TickDoneMsg::new(5, 7).pack());
+
+        let (header2, msg) = parse_message(&buf).unwrap();
+        assert_eq!(header, header2);
+        assert_eq!(
+            downcast::<TickDoneMsg>(&msg, "tick_done"),
+            &TickDoneMsg::new(5, 7)
+        );
+
+        // a build with a different protocol is turned away before its
+        // message gets misread
+        let mut other_version = buf.clone();
+        other_version[2] = PROTOCOL_VERSION + 1;
+        assert!(matches!(
+            parse_message(&other_version),
+            Err(CommsError::VersionMismatch { .. })
+        ));
+
+        // including one that's shorter than this version's header, like a
+        // version 1 kill msg
+        let mut old_kill = buf[..HEADER_LEN - 7].to_vec();
+        old_kill[2] = 1;
+        assert!(matches!(
+            parse_message(&old_kill),
+            Err(CommsError::VersionMismatch { version: 1 })
+        ));
+
+        assert!(matches!(
+            parse_message(&buf[HEADER_LEN..]),
+            Err(CommsError::BadMagic)
+        ));
+        assert!(matches!(
+            parse_message(&buf[..HEADER_LEN - 1]),
+            Err(CommsError::BadLength {
+                msg_label: "HEADER",
+                ..
+            })
+        ));
+
+        let header = MsgHeader::new(CONSOLE_ID, 1, None, 0);
+        let buf = frame_message(header, &KillMsg::new().pack());
+        assert_eq!(parse_message(&buf).unwrap().0.reply_to, None);
+
+        assert_eq!(next_seq(1), 2);
+        assert_eq!(next_seq(u32::MAX), 1);
+    }
+
+    fn many_readings(count: usize) -> ObsReadingsMsg {
+        let readings = (0..count)
+            .map(|i| Vec3d::new(i as f32, -(i as f32), 0.5))
+            .collect();
+        ObsReadingsMsg::new(readings)
+    }
+
+    fn reassemble(reassembler: &mut Reassembler, datagram: &[u8]) -> Option<ObsReadingsMsg> {
+        let buf = reassembler.add(datagram).unwrap()?;
+        let (header, msg) = parse_message(&buf).unwrap();
+        assert_eq!((header.frag_index, header.frag_count), (0, 1));
+        Some(downcast::<ObsReadingsMsg>(&msg, "obs_readings").clone())
+    }
+
+    #[test]
+    fn test_fragmentation() {
+        let msg = many_readings(1500);
+        let header = MsgHeader::new(CONSOLE_ID, 9, Some(4), 0);
+        let datagrams = fragment_message(header, &msg.pack()).unwrap();
+        assert!(datagrams.len() > 1);
+        assert!(datagrams.iter().all(|buf| buf.len() <= MAX_DATAGRAM_LEN));
+
+        // a fragment on its own isn't a message
+        assert!(matches!(
+            parse_message(&datagrams[0]),
+            Err(CommsError::BadFragment { index: 0, .. })
+        ));
+
+        // fragments can come in any order, mixed up with other messages and
+        // repeats
+        let other = fragment_message(MsgHeader::new(CONSOLE_ID, 10, None, 0), &msg.pack()).unwrap();
+        let mut reassembler = Reassembler::new();
+        for (i, datagram) in datagrams.iter().enumerate().skip(1).rev() {
+            assert_eq!(reassemble(&mut reassembler, datagram), None);
+            assert_eq!(reassemble(&mut reassembler, &other[i]), None);
+            assert_eq!(reassemble(&mut reassembler, datagram), None);
+        }
+        assert_eq!(
+            reassemble(&mut reassembler, &datagrams[0]),
+            Some(msg.clone())
+        );
+        assert_eq!(reassemble(&mut reassembler, &other[0]), Some(msg));
+
+        // the densest sensor allowed still fits, but anything much bigger is
+        // turned away rather than sent
+        let densest = many_readings(SensorConfig::MAX_RAYS as usize).pack();
+        assert!(fragment_message(header, &densest).is_ok());
+        let too_long = vec![0; MAX_FRAGMENTS * MAX_FRAGMENT_BODY_LEN + 1];
+        assert!(matches!(
+            fragment_message(header, &too_long),
+            Err(CommsError::TooLong(_))
+        ));
+
+        // small messages go as they are
+        let tick_done = TickDoneMsg::new(5, 7).pack();
+        let datagrams = fragment_message(header, &tick_done).unwrap();
+        assert_eq!(datagrams, vec![frame_message(header, &tick_done)]);
+        assert_eq!(
+            reassembler.add(&datagrams[0]).unwrap(),
+            Some(datagrams[0].clone())
+        );
+    }
+
+    #[test]
+    fn test_fragments_over_udp() {
+        let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let receiver = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+
+        let msg = many_readings(1000);
+        let header = MsgHeader::new(1, 1, None, timestamp_now_ms());
+        for datagram in fragment_message(header, &msg.pack()).unwrap() {
+            sender
+                .send_to(&datagram, receiver.local_addr().unwrap())
+                .unwrap();
+        }
+
+        let mut reassembler = Reassembler::new();
+        let mut buf = [0; MAX_DATAGRAM_LEN];
+        let received = loop {
+            let (len, _) = receiver.recv_from(&mut buf).unwrap();
+            if let Some(received) = reassemble(&mut reassembler, &buf[..len]) {
+                break received;
+            }
+        };
+        assert_eq!(received, msg);
+    }
+
+    #[test]
+    fn test_abandoned_fragments() {
+        let msg_buf = many_readings(200).pack();
+        let mut reassembler = Reassembler::new();
+        // every message is missing its last fragment
+        for seq in 1..=(Reassembler::MAX_PARTIAL_MSGS as u32 + 4) {
+            let datagrams = fragment_message(MsgHeader::new(1, seq, None, 0), &msg_buf).unwrap();
+            assert_eq!(reassemble(&mut reassembler, &datagrams[0]), None);
+        }
+        assert_eq!(
+            reassembler.partial_msgs.len(),
+            Reassembler::MAX_PARTIAL_MSGS
+        );
+        assert!(!reassembler.partial_msgs.contains_key(&(1, 1)));
+
+        // and a fragment that disagrees with the rest of its message is
+        // turned away
+        let mut datagram = fragment_message(MsgHeader::new(1, 20, None, 0), &msg_buf)
+            .unwrap()
+            .remove(1);
+        datagram[HEADER_LEN - 1] += 1;
+        assert!(matches!(
+            reassembler.add(&datagram),
+            Err(CommsError::BadFragment { .. })
+        ));
+        datagram[HEADER_LEN - 3] = 10;
+        assert!(matches!(
+            parse_message(&datagram),
+            Err(CommsError::BadFragment { index: 10, .. })
+        ));
+    }
+
+    #[test]
+    fn test_bad_messages() {
+        let header = MsgHeader::new(CONSOLE_ID, 1, None, 0);
+        let frame = |msg_buf: &[u8]| frame_message(header, msg_buf);
+
+        assert!(matches!(
+            parse_message(&frame(&[])),
+            Err(CommsError::BadLength { len: 0, .. })
+        ));
+        assert!(matches!(
+            parse_message(&frame(&[0xff])),
+            Err(CommsError::BadType(0xff))
+        ));
+
+        let pos = RobotPosition::new(Vec3d::new(1.0, 2.0, 0.0), 0.5);
+        let buf = PositionMsg::new(5, pos, 1.0).pack();
+        assert!(matches!(
+            parse_message(&frame(&buf[..buf.len() - 1])),
+            Err(CommsError::BadLength {
+                msg_label: "Position",
+                ..
+            })
+        ));
+
+        let buf = ObsReadingsMsg::new(vec![Vec3d::new(1.0, 2.0, 0.0)]).pack();
+        assert!(matches!(
+            parse_message(&frame(&buf[..buf.len() - 3])),
+            Err(CommsError::BadLength { .. })
+        ));
+    }
 }
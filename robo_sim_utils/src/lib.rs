@@ -1,8 +1,10 @@
 pub mod color;
 pub mod comms;
 pub mod messages;
+pub mod object_reading;
 pub mod ray;
 pub mod robot_position;
+pub mod sensor_config;
 pub mod vec3d;
 
 use std::f64::consts::PI;
@@ -52,6 +54,17 @@ pub fn normalize_angle_360<T: Float>(ang_deg: T) -> T {
     wrap_in_interval(ang_deg, my_zero, my_360)
 }
 
+/// Mixes a run's seed with a stream number (e.g. a robot ID) into a seed for
+/// that stream, so each robot gets its own well-separated sequence of random
+/// numbers no matter what order the others draw theirs in. This is the
+/// SplitMix64 finalizer.
+pub fn derive_seed(seed: u64, stream: u32) -> u64 {
+    let mut z = seed.wrapping_add((stream as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -92,4 +105,12 @@ mod tests {
         assert_eq!(normalize_angle_2pi(-HALF_PI), 1.5 * PI);
         assert_eq!(normalize_angle_2pi(-2.0 * PI), 0.0);
     }
+
+    #[test]
+    fn test_derive_seed() {
+        assert_eq!(derive_seed(42, 1), derive_seed(42, 1));
+        assert_ne!(derive_seed(42, 1), derive_seed(42, 2));
+        assert_ne!(derive_seed(42, 1), derive_seed(43, 1));
+        assert_ne!(derive_seed(0, 0), 0);
+    }
 }
@@ -1,7 +1,9 @@
 use std::any::Any;
 
 use super::color::Color;
+use super::object_reading::ObjectReading;
 use super::robot_position::RobotPosition;
+use super::sensor_config::SensorConfig;
 use super::vec3d::Vec3d;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -17,6 +19,15 @@ pub enum MessageType {
     Pause = 9,
     Move = 10,
     Spin = 11,
+    SensorConfig = 12,
+    GetObjects = 13,
+    ObjectReadings = 14,
+    Pickup = 15,
+    Drop = 16,
+    CarryStatus = 17,
+    GoalEvent = 18,
+    Tick = 19,
+    TickDone = 20,
 }
 
 pub trait Message {
@@ -102,12 +113,15 @@ impl Message for AliveMsg {
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct StartMsg {
     pub msg_type: MessageType,
+    // when set, the robot runs one cycle per Tick instead of free-running
+    pub lockstep: bool,
 }
 
 impl StartMsg {
-    pub fn new() -> Self {
+    pub fn new(lockstep: bool) -> Self {
         Self {
             msg_type: MessageType::Start,
+            lockstep: lockstep,
         }
     }
 }
@@ -164,14 +178,18 @@ pub struct PositionMsg {
     pub msg_type: MessageType,
     pub id: u32,
     pub pos: RobotPosition,
+    // simulated seconds since the console started; robots reporting their own
+    // position pass along the last one they heard
+    pub sim_time: f64,
 }
 
 impl PositionMsg {
-    pub fn new(id: u32, pos: RobotPosition) -> Self {
+    pub fn new(id: u32, pos: RobotPosition, sim_time: f64) -> Self {
         Self {
             msg_type: MessageType::Position,
             id: id,
             pos: pos,
+            sim_time: sim_time,
         }
     }
 }
@@ -408,3 +426,300 @@ impl Message for SpinMsg {
         self
     }
 }
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SensorConfigMsg {
+    pub msg_type: MessageType,
+    pub id: u32,
+    pub config: SensorConfig,
+}
+
+impl SensorConfigMsg {
+    pub fn new(id: u32, config: SensorConfig) -> Self {
+        Self {
+            msg_type: MessageType::SensorConfig,
+            id: id,
+            config: config,
+        }
+    }
+}
+
+impl Message for SensorConfigMsg {
+    fn get_type(&self) -> MessageType {
+        self.msg_type
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GetObjectsMsg {
+    pub msg_type: MessageType,
+    pub id: u32,
+    pub max_range: f32,
+    pub fov_rad: f32,
+}
+
+impl GetObjectsMsg {
+    pub fn new(id: u32, max_range: f32, fov_rad: f32) -> Self {
+        Self {
+            msg_type: MessageType::GetObjects,
+            id: id,
+            max_range: max_range,
+            fov_rad: fov_rad,
+        }
+    }
+}
+
+impl Message for GetObjectsMsg {
+    fn get_type(&self) -> MessageType {
+        self.msg_type
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ObjectReadingsMsg {
+    pub msg_type: MessageType,
+    pub readings: Vec<ObjectReading>,
+}
+
+impl ObjectReadingsMsg {
+    pub fn new(readings: Vec<ObjectReading>) -> Self {
+        Self {
+            msg_type: MessageType::ObjectReadings,
+            readings: readings,
+        }
+    }
+}
+
+impl Message for ObjectReadingsMsg {
+    fn get_type(&self) -> MessageType {
+        self.msg_type
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PickupMsg {
+    pub msg_type: MessageType,
+    pub id: u32,
+}
+
+impl PickupMsg {
+    pub fn new(id: u32) -> Self {
+        Self {
+            msg_type: MessageType::Pickup,
+            id: id,
+        }
+    }
+}
+
+impl Message for PickupMsg {
+    fn get_type(&self) -> MessageType {
+        self.msg_type
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DropMsg {
+    pub msg_type: MessageType,
+    pub id: u32,
+}
+
+impl DropMsg {
+    pub fn new(id: u32) -> Self {
+        Self {
+            msg_type: MessageType::Drop,
+            id: id,
+        }
+    }
+}
+
+impl Message for DropMsg {
+    fn get_type(&self) -> MessageType {
+        self.msg_type
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CarryStatusMsg {
+    pub msg_type: MessageType,
+    pub id: u32,
+    pub carrying: bool,
+}
+
+impl CarryStatusMsg {
+    pub fn new(id: u32, carrying: bool) -> Self {
+        Self {
+            msg_type: MessageType::CarryStatus,
+            id: id,
+            carrying: carrying,
+        }
+    }
+}
+
+impl Message for CarryStatusMsg {
+    fn get_type(&self) -> MessageType {
+        self.msg_type
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GoalEventMsg {
+    pub msg_type: MessageType,
+    pub id: u32,
+    pub goal_id: u32,
+    pub entered: bool,
+    pub elapsed_s: f32,
+}
+
+impl GoalEventMsg {
+    pub fn new(id: u32, goal_id: u32, entered: bool, elapsed_s: f32) -> Self {
+        Self {
+            msg_type: MessageType::GoalEvent,
+            id: id,
+            goal_id: goal_id,
+            entered: entered,
+            elapsed_s: elapsed_s,
+        }
+    }
+}
+
+impl Message for GoalEventMsg {
+    fn get_type(&self) -> MessageType {
+        self.msg_type
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TickMsg {
+    pub msg_type: MessageType,
+    pub tick: u64,
+    pub sim_time: f64,
+}
+
+impl TickMsg {
+    pub fn new(tick: u64, sim_time: f64) -> Self {
+        Self {
+            msg_type: MessageType::Tick,
+            tick: tick,
+            sim_time: sim_time,
+        }
+    }
+}
+
+impl Message for TickMsg {
+    fn get_type(&self) -> MessageType {
+        self.msg_type
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TickDoneMsg {
+    pub msg_type: MessageType,
+    pub id: u32,
+    pub tick: u64,
+}
+
+impl TickDoneMsg {
+    pub fn new(id: u32, tick: u64) -> Self {
+        Self {
+            msg_type: MessageType::TickDone,
+            id: id,
+            tick: tick,
+        }
+    }
+}
+
+impl Message for TickDoneMsg {
+    fn get_type(&self) -> MessageType {
+        self.msg_type
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
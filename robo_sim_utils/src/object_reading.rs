@@ -0,0 +1,21 @@
+use super::color::Color;
+use super::vec3d::Vec3d;
+
+/// A colored object as seen by a robot, with its position egocentric to the
+/// robot.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ObjectReading {
+    pub pos: Vec3d<f32>,
+    pub radius: f32,
+    pub color: Color,
+}
+
+impl ObjectReading {
+    pub fn new(pos: Vec3d<f32>, radius: f32, color: Color) -> Self {
+        Self {
+            pos: pos,
+            radius: radius,
+            color: color,
+        }
+    }
+}
@@ -2,6 +2,15 @@ use num_traits::Float;
 
 use super::vec3d::Vec3d;
 
+/// Where a circle swept along a ray first touches something: `t` is the
+/// fraction of the ray travelled (0 to 1) and `normal` is the unit contact
+/// normal, pointing from the obstacle toward the swept circle.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SweepHit<T: Float> {
+    pub t: T,
+    pub normal: Vec3d<T>,
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Ray<T: Float> {
     pub from_loc: Vec3d<T>,
@@ -104,4 +113,267 @@ impl<T: Float> Ray<T> {
 
         intersection
     }
+
+    pub fn sweep_circle_against_circle_2d(
+        &self,
+        radius: T,
+        xc: T,
+        yc: T,
+        circle_radius: T,
+    ) -> Option<SweepHit<T>> {
+        let zero = T::zero();
+        let one = T::one();
+        let two = T::from(2.0).unwrap();
+        let four = T::from(4.0).unwrap();
+
+        let d = self.to_loc - self.from_loc;
+        let m = self.from_loc - Vec3d::new(xc, yc, zero);
+        let m = Vec3d::new(m.x, m.y, zero);
+        let d = Vec3d::new(d.x, d.y, zero);
+        let combined_radius = radius + circle_radius;
+
+        // already touching: only blocked if moving further in
+        if m.len_sq() <= combined_radius * combined_radius {
+            let normal = if m.len_sq() > zero { m.to_unit() } else { -d.to_unit() };
+            if d.dot(normal) < zero {
+                return Some(SweepHit { t: zero, normal: normal });
+            }
+            return None;
+        }
+
+        let a = d.len_sq();
+        if a == zero {
+            return None;
+        }
+
+        let b = two * m.dot(d);
+        let c = m.len_sq() - combined_radius * combined_radius;
+        let quot = (b * b) - four * a * c;
+        if quot < zero {
+            return None;
+        }
+
+        let t = (-b - quot.sqrt()) / (two * a);
+        if t < zero || t > one {
+            return None;
+        }
+
+        let normal = (m + d * t) / combined_radius;
+        if d.dot(normal) >= zero {
+            // grazing contact, the circle slides right past
+            return None;
+        }
+
+        Some(SweepHit { t: t, normal: normal })
+    }
+
+    pub fn sweep_circle_against_segment_2d(
+        &self,
+        radius: T,
+        xs0: T,
+        ys0: T,
+        xs1: T,
+        ys1: T,
+    ) -> Option<SweepHit<T>> {
+        let zero = T::zero();
+        let one = T::one();
+
+        let p0 = Vec3d::new(self.from_loc.x, self.from_loc.y, zero);
+        let d = Vec3d::new(self.to_loc.x - p0.x, self.to_loc.y - p0.y, zero);
+        let a = Vec3d::new(xs0, ys0, zero);
+        let e = Vec3d::new(xs1 - xs0, ys1 - ys0, zero);
+
+        let end_hit_0 = self.sweep_circle_against_circle_2d(radius, xs0, ys0, zero);
+        let end_hit_1 = self.sweep_circle_against_circle_2d(radius, xs1, ys1, zero);
+
+        if e.len_sq() == zero {
+            return end_hit_0;
+        }
+
+        // already touching the segment's face: only blocked if moving further in
+        let u0 = ((p0 - a).dot(e) / e.len_sq()).max(zero).min(one);
+        let closest = a + e * u0;
+        let face_hit = if (p0 - closest).len_sq() <= radius * radius {
+            let offset = p0 - closest;
+            if offset.len_sq() > zero && d.dot(offset.to_unit()) < zero {
+                Some(SweepHit {
+                    t: zero,
+                    normal: offset.to_unit(),
+                })
+            } else {
+                None
+            }
+        } else {
+            // the face of the segment, pushed out toward the circle by its radius
+            let perp = Vec3d::new(-e.y, e.x, zero).to_unit();
+            let side_dist = (p0 - a).dot(perp);
+            let normal = if side_dist >= zero { perp } else { -perp };
+            let dist = side_dist.abs();
+            let approach = d.dot(normal);
+
+            if approach < zero {
+                let t = (radius - dist) / approach;
+                let contact = p0 + d * t - normal * radius;
+                let u = (contact - a).dot(e) / e.len_sq();
+                if t >= zero && t <= one && u >= zero && u <= one {
+                    Some(SweepHit { t: t, normal: normal })
+                } else {
+                    None
+                }
+            } else {
+                None
+            }
+        };
+
+        // the endpoints catch the corner cases the face test misses
+        vec![face_hit, end_hit_0, end_hit_1]
+            .into_iter()
+            .flatten()
+            .reduce(|accum, hit| if accum.t <= hit.t { accum } else { hit })
+    }
+
+    /// Closest point where the ray crosses the edge of a closed polygon. The
+    /// last point connects back to the first.
+    pub fn intersect_with_polygon_2d(&self, points: &[Vec3d<T>]) -> Option<Vec3d<T>> {
+        polygon_edges(points)
+            .filter_map(|(p0, p1)| self.intersect_with_segment_2d(p0.x, p0.y, p1.x, p1.y))
+            .map(|pt| ((pt - self.from_loc).len_sq(), pt))
+            .reduce(|accum, hit| if accum.0 <= hit.0 { accum } else { hit })
+            .map(|(_, pt)| pt)
+    }
+
+    pub fn sweep_circle_against_polygon_2d(
+        &self,
+        radius: T,
+        points: &[Vec3d<T>],
+    ) -> Option<SweepHit<T>> {
+        polygon_edges(points)
+            .filter_map(|(p0, p1)| {
+                self.sweep_circle_against_segment_2d(radius, p0.x, p0.y, p1.x, p1.y)
+            })
+            .reduce(|accum, hit| if accum.t <= hit.t { accum } else { hit })
+    }
+}
+
+fn polygon_edges<T: Float>(
+    points: &[Vec3d<T>],
+) -> impl Iterator<Item = (Vec3d<T>, Vec3d<T>)> + '_ {
+    let n = points.len();
+    (0..n).map(move |i| (points[i], points[(i + 1) % n]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS: f32 = 0.00001;
+
+    fn moving_right() -> Ray<f32> {
+        Ray::new(Vec3d::new(0.0, 0.0, 0.0), Vec3d::new(10.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn test_sweep_circle_head_on() {
+        let hit = moving_right()
+            .sweep_circle_against_circle_2d(0.5, 5.0, 0.0, 1.0)
+            .unwrap();
+        assert!((hit.t - 0.35).abs() < EPS);
+        assert!((hit.normal - Vec3d::new(-1.0, 0.0, 0.0)).len() < EPS);
+    }
+
+    #[test]
+    fn test_sweep_circle_tangential() {
+        // just clear of the obstacle
+        let hit = moving_right().sweep_circle_against_circle_2d(0.5, 5.0, 1.01, 0.5);
+        assert_eq!(hit, None);
+
+        // clips the edge of the obstacle even though the center line misses it
+        let hit = moving_right()
+            .sweep_circle_against_circle_2d(0.5, 5.0, 0.99, 0.5)
+            .unwrap();
+        let expected_t = (5.0 - (1.0f32 - 0.99 * 0.99).sqrt()) / 10.0;
+        assert!((hit.t - expected_t).abs() < EPS);
+        assert!(hit.normal.y < 0.0);
+    }
+
+    #[test]
+    fn test_sweep_circle_already_touching() {
+        // touching and moving away isn't a collision
+        let ray = Ray::new(Vec3d::new(4.0, 0.0, 0.0), Vec3d::new(0.0, 0.0, 0.0));
+        assert_eq!(ray.sweep_circle_against_circle_2d(0.5, 5.0, 0.0, 0.5), None);
+
+        // touching and moving in is blocked immediately
+        let ray = Ray::new(Vec3d::new(4.0, 0.0, 0.0), Vec3d::new(6.0, 0.0, 0.0));
+        let hit = ray.sweep_circle_against_circle_2d(0.5, 5.0, 0.0, 0.5).unwrap();
+        assert_eq!(hit.t, 0.0);
+    }
+
+    #[test]
+    fn test_sweep_segment_face() {
+        let hit = moving_right()
+            .sweep_circle_against_segment_2d(0.5, 5.0, -5.0, 5.0, 5.0)
+            .unwrap();
+        assert!((hit.t - 0.45).abs() < EPS);
+        assert!((hit.normal - Vec3d::new(-1.0, 0.0, 0.0)).len() < EPS);
+    }
+
+    #[test]
+    fn test_sweep_segment_corner() {
+        // the center line passes below the wall's end, but the body doesn't
+        let hit = moving_right()
+            .sweep_circle_against_segment_2d(0.5, 5.0, 0.3, 5.0, 5.0)
+            .unwrap();
+        assert!((hit.t - 0.46).abs() < EPS);
+        assert!((hit.normal - Vec3d::new(-0.8, -0.6, 0.0)).len() < EPS);
+
+        // and far enough below, it misses entirely
+        let hit = moving_right().sweep_circle_against_segment_2d(0.5, 5.0, 0.6, 5.0, 5.0);
+        assert_eq!(hit, None);
+    }
+
+    #[test]
+    fn test_sweep_segment_touching_face_toward_end() {
+        // touching the wall's face and heading for its end still blocks
+        // straight away, rather than at the end
+        let ray = Ray::new(Vec3d::new(4.5, 4.0, 0.0), Vec3d::new(6.0, 5.5, 0.0));
+        let hit = ray
+            .sweep_circle_against_segment_2d(0.5, 5.0, -5.0, 5.0, 5.0)
+            .unwrap();
+        assert_eq!(hit.t, 0.0);
+        assert!((hit.normal - Vec3d::new(-1.0, 0.0, 0.0)).len() < EPS);
+    }
+
+    #[test]
+    fn test_sweep_segment_parallel() {
+        // sliding alongside a wall at exactly the radius doesn't stop the circle
+        let hit = moving_right().sweep_circle_against_segment_2d(0.5, 2.0, 0.5, 8.0, 0.5);
+        assert_eq!(hit, None);
+    }
+
+    fn square() -> Vec<Vec3d<f32>> {
+        vec![
+            Vec3d::new(4.0, -1.0, 0.0),
+            Vec3d::new(6.0, -1.0, 0.0),
+            Vec3d::new(6.0, 1.0, 0.0),
+            Vec3d::new(4.0, 1.0, 0.0),
+        ]
+    }
+
+    #[test]
+    fn test_intersect_polygon_takes_nearest_edge() {
+        let hit = moving_right().intersect_with_polygon_2d(&square()).unwrap();
+        assert!((hit - Vec3d::new(4.0, 0.0, 0.0)).len() < EPS);
+
+        let ray = Ray::new(Vec3d::new(0.0, 5.0, 0.0), Vec3d::new(10.0, 5.0, 0.0));
+        assert_eq!(ray.intersect_with_polygon_2d(&square()), None);
+    }
+
+    #[test]
+    fn test_sweep_polygon() {
+        let hit = moving_right()
+            .sweep_circle_against_polygon_2d(0.5, &square())
+            .unwrap();
+        assert!((hit.t - 0.35).abs() < EPS);
+        assert!((hit.normal - Vec3d::new(-1.0, 0.0, 0.0)).len() < EPS);
+    }
 }
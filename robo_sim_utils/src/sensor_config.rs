@@ -0,0 +1,91 @@
+use std::f32::consts::PI;
+
+/// How a robot's simulated obstacle sensor is laid out and how imperfect its
+/// readings are. Angles are in radians, ranges in meters.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SensorConfig {
+    pub num_rays: u32,
+    pub fov_rad: f32,
+    pub max_range: f32,
+    pub angle_offset_rad: f32,
+    pub range_noise_std_dev: f32,
+    pub dropout_prob: f32,
+}
+
+impl SensorConfig {
+    // any more and a single set of readings gets too long to send
+    pub const MAX_RAYS: u32 = 10_000;
+
+    pub fn new(
+        num_rays: u32,
+        fov_rad: f32,
+        max_range: f32,
+        angle_offset_rad: f32,
+        range_noise_std_dev: f32,
+        dropout_prob: f32,
+    ) -> Self {
+        Self {
+            num_rays: num_rays,
+            fov_rad: fov_rad,
+            max_range: max_range,
+            angle_offset_rad: angle_offset_rad,
+            range_noise_std_dev: range_noise_std_dev,
+            dropout_prob: dropout_prob,
+        }
+    }
+
+    /// Angle of the ray_num'th ray relative to the robot's heading. A full
+    /// circle spaces the rays evenly all the way around; anything narrower
+    /// puts the first and last rays on the edges of the field of view.
+    pub fn ray_angle(&self, ray_num: u32) -> f32 {
+        const FULL_CIRCLE_RAD: f32 = 2.0 * PI;
+        // a 360 degree fov that's been through a degrees round trip can come
+        // back a hair short of 2pi
+        const FULL_CIRCLE_TOLERANCE: f32 = 0.0001;
+
+        if self.fov_rad >= FULL_CIRCLE_RAD - FULL_CIRCLE_TOLERANCE {
+            let rad_per_ray = FULL_CIRCLE_RAD / (self.num_rays as f32);
+            return self.angle_offset_rad + (ray_num as f32) * rad_per_ray;
+        }
+
+        if self.num_rays <= 1 {
+            return self.angle_offset_rad;
+        }
+
+        let rad_per_ray = self.fov_rad / ((self.num_rays - 1) as f32);
+        self.angle_offset_rad - (self.fov_rad / 2.0) + (ray_num as f32) * rad_per_ray
+    }
+}
+
+impl Default for SensorConfig {
+    // 16 perfect, unlimited-range rays all the way around
+    fn default() -> Self {
+        Self::new(16, 2.0 * PI, f32::INFINITY, 0.0, 0.0, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS: f32 = 0.00001;
+
+    #[test]
+    fn test_ray_angle_full_circle() {
+        let config = SensorConfig::default();
+        assert_eq!(config.ray_angle(0), 0.0);
+        assert!((config.ray_angle(4) - PI / 2.0).abs() < EPS);
+        assert!((config.ray_angle(15) - 15.0 * PI / 8.0).abs() < EPS);
+    }
+
+    #[test]
+    fn test_ray_angle_partial_fov() {
+        let config = SensorConfig::new(3, PI / 2.0, 5.0, 0.1, 0.0, 0.0);
+        assert!((config.ray_angle(0) - (0.1 - PI / 4.0)).abs() < EPS);
+        assert!((config.ray_angle(1) - 0.1).abs() < EPS);
+        assert!((config.ray_angle(2) - (0.1 + PI / 4.0)).abs() < EPS);
+
+        let config = SensorConfig::new(1, PI / 2.0, 5.0, 0.1, 0.0, 0.0);
+        assert_eq!(config.ray_angle(0), 0.1);
+    }
+}